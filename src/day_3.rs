@@ -1,42 +1,466 @@
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+
+/// The items that can appear in rucksacks, ordered by priority.
+///
+/// The first item has a priority of 1, the second a priority of 2, and so on. Each item is mapped
+/// to a bit of a `u64`, so an alphabet can hold at most 64 items.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Alphabet {
+    items: Vec<char>,
+    bits: HashMap<char, u32>,
+}
+
+impl Alphabet {
+    /// Creates an [`Alphabet`] from its items, in increasing priority order.
+    ///
+    /// # Panics
+    /// Panics if `items` has more than 64 characters or contains the same character twice
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use advent_of_code_2022::day_3::Alphabet;
+    ///
+    /// let alphabet = Alphabet::new("xyz");
+    /// assert_eq!(alphabet.priority('y'), Some(2));
+    /// assert_eq!(alphabet.priority('a'), None);
+    /// ```
+    #[must_use]
+    pub fn new(items: &str) -> Self {
+        let items: Vec<char> = items.chars().collect();
+
+        assert!(
+            items.len() <= 64,
+            "Alphabet has {} items but at most 64 are supported",
+            items.len()
+        );
+
+        let mut bits = HashMap::with_capacity(items.len());
+
+        for (bit, item) in (0..).zip(&items) {
+            if bits.insert(*item, bit).is_some() {
+                panic!("Duplicate item in alphabet: {item:?}");
+            }
+        }
+
+        Self { items, bits }
+    }
+
+    /// Returns the priority of an item, or `None` if it's not part of the alphabet
+    #[must_use]
+    pub fn priority(&self, item: char) -> Option<usize> {
+        self.bits.get(&item).map(|bit| *bit as usize + 1)
+    }
+
+    fn bit(&self, item: char) -> Option<u32> {
+        self.bits.get(&item).copied()
+    }
+
+    /// Returns the distinct items of `items` that are not part of the alphabet, in order
+    fn unknown_items(&self, items: &str) -> Vec<char> {
+        let mut result = vec![];
+
+        for c in items.chars() {
+            if !self.bits.contains_key(&c) && !result.contains(&c) {
+                result.push(c);
+            }
+        }
+
+        result
+    }
+}
+
+impl Default for Alphabet {
+    /// The puzzle alphabet: `a` through `z` then `A` through `Z`
+    fn default() -> Self {
+        Self::new("abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ")
+    }
+}
+
+/// A set of items, stored as a bit mask over an [`Alphabet`].
+///
+/// Duplicates are irrelevant to the puzzle, so a rucksack only remembers which items it holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Rucksack {
+    items: u64,
+}
+
+impl Rucksack {
+    /// Builds a [`Rucksack`] holding every item of `items`.
+    ///
+    /// # Panics
+    /// Panics if an item is not part of `alphabet`
+    #[must_use]
+    pub fn from_items(items: &str, alphabet: &Alphabet) -> Self {
+        Self {
+            items: items.chars().fold(0, |acc, c| {
+                let bit = alphabet
+                    .bit(c)
+                    .unwrap_or_else(|| panic!("Unknown char: {c}"));
+
+                acc | 1 << bit
+            }),
+        }
+    }
+
+    /// Splits a line in two halves and returns the rucksack of each compartment
+    ///
+    /// # Panics
+    /// Panics if the line has an odd length or if an item is not part of `alphabet`
+    #[must_use]
+    pub fn compartments(line: &str, alphabet: &Alphabet) -> (Self, Self) {
+        assert!(
+            line.chars().count().is_multiple_of(2),
+            "Odd-length line cannot be split in two compartments: {line}"
+        );
+
+        // Splitting on the char boundary keeps non-ASCII alphabets working
+        let middle = line
+            .char_indices()
+            .nth(line.chars().count() / 2)
+            .map_or(line.len(), |(idx, _)| idx);
+        let (left, right) = line.split_at(middle);
+
+        (
+            Self::from_items(left, alphabet),
+            Self::from_items(right, alphabet),
+        )
+    }
+
+    /// Returns the items present in both rucksacks
+    #[must_use]
+    pub const fn intersection(self, other: Self) -> Self {
+        Self {
+            items: self.items & other.items,
+        }
+    }
+
+    /// Returns the items present in either rucksack
+    #[must_use]
+    pub const fn union(self, other: Self) -> Self {
+        Self {
+            items: self.items | other.items,
+        }
+    }
+
+    /// Returns the number of distinct items in the rucksack
+    #[must_use]
+    pub const fn len(self) -> usize {
+        self.items.count_ones() as usize
+    }
+
+    #[must_use]
+    pub const fn is_empty(self) -> bool {
+        self.items == 0
+    }
+
+    #[must_use]
+    pub fn contains(self, item: char, alphabet: &Alphabet) -> bool {
+        alphabet
+            .bit(item)
+            .is_some_and(|bit| self.items & 1 << bit != 0)
+    }
+
+    /// Iterates on the items of the rucksack, in increasing priority order
+    pub fn items(self, alphabet: &Alphabet) -> impl Iterator<Item = char> + '_ {
+        alphabet
+            .items
+            .iter()
+            .enumerate()
+            .filter(move |(bit, _)| self.items & 1 << bit != 0)
+            .map(|(_, item)| *item)
+    }
+
+    /// Returns the priority of the lowest-priority item in the rucksack, or `None` if it is empty
+    #[must_use]
+    pub const fn lowest_priority(self) -> Option<usize> {
+        if self.items == 0 {
+            None
+        } else {
+            Some(self.items.trailing_zeros() as usize + 1)
+        }
+    }
+
+    /// Returns the sum of the priorities of all items in the rucksack
+    #[must_use]
+    pub const fn total_priority(self) -> usize {
+        // Priorities are bit indices + 1, so we walk the set bits
+        let mut items = self.items;
+        let mut result = 0;
+
+        while items != 0 {
+            result += items.trailing_zeros() as usize + 1;
+            items &= items - 1;
+        }
+
+        result
+    }
+}
+
+/// Returns the sum of the priorities of the item shared by the two compartments of each line.
+///
+/// If a line shares several items, only the lowest-priority one is counted.
+///
 /// # Panics
-/// Panics if no match is found for a line
+/// Panics if no match is found for a line or if a line has an odd length
 #[must_use]
 pub fn sum_priorities(input: &str) -> usize {
-    input.lines().fold(0, |acc, line| {
-        let (left, right) = line.split_at(line.len() / 2);
+    sum_priorities_with_alphabet(input, &Alphabet::default())
+}
 
-        for c in left.chars() {
-            if right.contains(c) {
-                return acc + get_priority(c);
-            }
-        }
+/// Same as [`sum_priorities`], with a custom [`Alphabet`].
+///
+/// # Panics
+/// Panics if no match is found for a line, if a line has an odd length or if an item is not
+/// part of `alphabet`
+#[must_use]
+pub fn sum_priorities_with_alphabet(input: &str, alphabet: &Alphabet) -> usize {
+    input.lines().fold(0, |acc, line| {
+        let (left, right) = Rucksack::compartments(line, alphabet);
+        let priority = left
+            .intersection(right)
+            .lowest_priority()
+            .unwrap_or_else(|| panic!("No match found for line: {line}"));
 
-        panic!("No match found for line: {line}");
+        acc + priority
     })
 }
 
 #[must_use]
 pub fn sum_triple_priorities(input: &str) -> usize {
-    input.lines().array_chunks::<3>().fold(0, |acc, lines| {
-        for c in lines[0].chars() {
-            if lines[1].contains(c) && lines[2].contains(c) {
-                return acc + get_priority(c);
-            }
-        }
+    sum_group_priorities(input, 3, &Alphabet::default())
+}
+
+/// Returns the sum of the priorities of the item shared by each group of `group_size` lines.
+///
+/// Trailing lines that do not make a full group are ignored. If a group shares several items,
+/// only the lowest-priority one is counted.
+///
+/// # Panics
+/// Panics if `group_size` is 0, if no match is found for a group or if an item is not part of
+/// `alphabet`
+///
+/// # Examples
+///
+/// ```
+/// use advent_of_code_2022::day_3::{sum_group_priorities, Alphabet};
+///
+/// let input = "abc
+/// bcd
+/// cde
+/// cfg";
+///
+/// assert_eq!(sum_group_priorities(input, 2, &Alphabet::default()), 2 + 3);
+/// assert_eq!(sum_group_priorities(input, 4, &Alphabet::default()), 3);
+/// ```
+#[must_use]
+pub fn sum_group_priorities(input: &str, group_size: usize, alphabet: &Alphabet) -> usize {
+    let lines: Vec<&str> = input.lines().collect();
 
-        panic!("No match found for lines: {lines:?}");
+    lines.chunks_exact(group_size).fold(0, |acc, lines| {
+        let priority = lines
+            .iter()
+            .map(|line| Rucksack::from_items(line, alphabet))
+            .reduce(Rucksack::intersection)
+            .unwrap_or_default()
+            .lowest_priority()
+            .unwrap_or_else(|| panic!("No match found for lines: {lines:?}"));
+
+        acc + priority
     })
 }
 
-// Hardcoded constant with space first is simple and *very* fast
-const ALPHABET: &str = " abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+/// The outcome of looking for the shared item of a rucksack or a group
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Status {
+    /// Exactly one item is shared
+    Valid(char),
+    /// Nothing is shared
+    NoOverlap,
+    /// More than one item is shared
+    AmbiguousOverlap(Vec<char>),
+    /// The line cannot be split in two compartments of the same size
+    OddLength(usize),
+    /// Some items are not part of the alphabet
+    UnknownItems(Vec<char>),
+    /// The group has fewer lines than the group size
+    IncompleteGroup(usize),
+}
+
+impl Status {
+    #[must_use]
+    pub const fn is_valid(&self) -> bool {
+        matches!(self, Self::Valid(_))
+    }
+
+    fn from_shared(shared: Rucksack, alphabet: &Alphabet) -> Self {
+        match shared.len() {
+            0 => Self::NoOverlap,
+            1 => Self::Valid(
+                shared
+                    .items(alphabet)
+                    .next()
+                    .expect("Rucksack of length 1 has an item"),
+            ),
+            _ => Self::AmbiguousOverlap(shared.items(alphabet).collect()),
+        }
+    }
+}
+
+impl Display for Status {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Valid(item) => write!(f, "valid, shares {item:?}"),
+            Self::NoOverlap => write!(f, "no shared item"),
+            Self::AmbiguousOverlap(items) => write!(f, "ambiguous, shares {items:?}"),
+            Self::OddLength(length) => write!(f, "odd length of {length}"),
+            Self::UnknownItems(items) => write!(f, "unknown items {items:?}"),
+            Self::IncompleteGroup(size) => write!(f, "incomplete group of {size} lines"),
+        }
+    }
+}
+
+/// The status of a single line, numbered from 1
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RucksackReport {
+    pub line: usize,
+    pub status: Status,
+}
+
+/// The status of a group of lines, numbered from 1
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupReport {
+    pub lines: Vec<usize>,
+    pub status: Status,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationReport {
+    pub rucksacks: Vec<RucksackReport>,
+    pub groups: Vec<GroupReport>,
+}
+
+impl ValidationReport {
+    #[must_use]
+    pub fn is_valid(&self) -> bool {
+        self.rucksacks.iter().all(|r| r.status.is_valid())
+            && self.groups.iter().all(|g| g.status.is_valid())
+    }
+
+    /// Returns the rucksacks that are not valid
+    pub fn invalid_rucksacks(&self) -> impl Iterator<Item = &RucksackReport> {
+        self.rucksacks.iter().filter(|r| !r.status.is_valid())
+    }
+
+    /// Returns the groups that are not valid
+    pub fn invalid_groups(&self) -> impl Iterator<Item = &GroupReport> {
+        self.groups.iter().filter(|g| !g.status.is_valid())
+    }
+}
+
+impl Display for ValidationReport {
+    /// Lists every issue, one per line
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for rucksack in self.invalid_rucksacks() {
+            writeln!(f, "Line {}: {}", rucksack.line, rucksack.status)?;
+        }
+
+        for group in self.invalid_groups() {
+            writeln!(f, "Group {:?}: {}", group.lines, group.status)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Checks every rucksack and every group of `group_size` lines of the input.
+///
+/// Unlike [`sum_priorities`] and [`sum_group_priorities`], this never panics on bad input.
+///
+/// # Panics
+/// Panics if `group_size` is 0
+///
+/// # Examples
+///
+/// ```
+/// use advent_of_code_2022::day_3::{validate, Alphabet, Status};
+///
+/// let report = validate("abcb\nabcd\nabc", 3, &Alphabet::default());
+///
+/// assert_eq!(report.rucksacks[0].status, Status::Valid('b'));
+/// assert_eq!(report.rucksacks[1].status, Status::NoOverlap);
+/// assert_eq!(report.rucksacks[2].status, Status::OddLength(3));
+/// assert_eq!(report.groups[0].status, Status::AmbiguousOverlap(vec!['a', 'b', 'c']));
+/// assert!(!report.is_valid());
+/// ```
+#[must_use]
+pub fn validate(input: &str, group_size: usize, alphabet: &Alphabet) -> ValidationReport {
+    assert!(group_size > 0, "Group size must be positive");
+
+    let lines: Vec<&str> = input.lines().collect();
+
+    let rucksacks = lines
+        .iter()
+        .enumerate()
+        .map(|(idx, line)| RucksackReport {
+            line: idx + 1,
+            status: rucksack_status(line, alphabet),
+        })
+        .collect();
 
-fn get_priority(c: char) -> usize {
-    ALPHABET
-        .chars()
-        .into_iter()
-        .position(|x| x == c)
-        .map_or_else(|| panic!("Unknown char: {c}"), |x| x)
+    let groups = lines
+        .chunks(group_size)
+        .enumerate()
+        .map(|(group_idx, group)| {
+            let first_line = group_idx * group_size + 1;
+
+            GroupReport {
+                lines: (first_line..first_line + group.len()).collect(),
+                status: group_status(group, group_size, alphabet),
+            }
+        })
+        .collect();
+
+    ValidationReport { rucksacks, groups }
+}
+
+fn rucksack_status(line: &str, alphabet: &Alphabet) -> Status {
+    let unknown_items = alphabet.unknown_items(line);
+
+    if !unknown_items.is_empty() {
+        return Status::UnknownItems(unknown_items);
+    }
+
+    let length = line.chars().count();
+
+    if !length.is_multiple_of(2) {
+        return Status::OddLength(length);
+    }
+
+    let (left, right) = Rucksack::compartments(line, alphabet);
+
+    Status::from_shared(left.intersection(right), alphabet)
+}
+
+fn group_status(group: &[&str], group_size: usize, alphabet: &Alphabet) -> Status {
+    if group.len() < group_size {
+        return Status::IncompleteGroup(group.len());
+    }
+
+    let unknown_items = alphabet.unknown_items(&group.concat());
+
+    if !unknown_items.is_empty() {
+        return Status::UnknownItems(unknown_items);
+    }
+
+    let shared = group
+        .iter()
+        .map(|line| Rucksack::from_items(line, alphabet))
+        .reduce(Rucksack::intersection)
+        .unwrap_or_default();
+
+    Status::from_shared(shared, alphabet)
 }
 
 #[cfg(test)]
@@ -60,14 +484,142 @@ CrZsJsPPZsGzwwsLwLmpwMDw";
         assert_eq!(sum_triple_priorities(DEMO_INPUT), 70);
     }
 
+    #[test]
+    fn test_sum_group_priorities() {
+        let alphabet = Alphabet::default();
+
+        assert_eq!(sum_group_priorities(DEMO_INPUT, 3, &alphabet), 70);
+        // Each line is its own group, so only its lowest item counts
+        assert_eq!(sum_group_priorities("ab\naA", 1, &alphabet), 1 + 1);
+        assert_eq!(
+            sum_group_priorities("abc\nbcd\ncde\ncfg", 2, &alphabet),
+            2 + 3
+        );
+    }
+
     #[test]
     fn test_prio() {
-        assert_eq!(get_priority('a'), 1);
-        assert_eq!(get_priority('b'), 2);
-        assert_eq!(get_priority('z'), 26);
-        assert_eq!(get_priority('A'), 27);
-        assert_eq!(get_priority('B'), 28);
-        assert_eq!(get_priority('Z'), 52);
+        let alphabet = Alphabet::default();
+
+        assert_eq!(alphabet.priority('a'), Some(1));
+        assert_eq!(alphabet.priority('b'), Some(2));
+        assert_eq!(alphabet.priority('z'), Some(26));
+        assert_eq!(alphabet.priority('A'), Some(27));
+        assert_eq!(alphabet.priority('B'), Some(28));
+        assert_eq!(alphabet.priority('Z'), Some(52));
+        assert_eq!(alphabet.priority('秘'), None);
+    }
+
+    #[test]
+    fn test_custom_alphabet() {
+        let alphabet = Alphabet::new("秘abc");
+
+        assert_eq!(sum_priorities_with_alphabet("a秘b秘", &alphabet), 1);
+        assert_eq!(sum_priorities_with_alphabet("acbc", &alphabet), 4);
+        assert_eq!(sum_priorities_with_alphabet("abcabc", &alphabet), 2);
+    }
+
+    #[test]
+    fn test_rucksack() {
+        let alphabet = Alphabet::default();
+        let (left, right) = Rucksack::compartments("abcZbcdZ", &alphabet);
+
+        assert_eq!(left.len(), 4);
+        assert!(left.contains('Z', &alphabet));
+        assert!(!left.contains('d', &alphabet));
+
+        let shared = left.intersection(right);
+        assert_eq!(shared.items(&alphabet).collect::<String>(), "bcZ");
+        assert_eq!(shared.lowest_priority(), Some(2));
+        assert_eq!(shared.total_priority(), 2 + 3 + 52);
+        assert_eq!(Rucksack::default().lowest_priority(), None);
+
+        assert_eq!(
+            left.union(right).items(&alphabet).collect::<String>(),
+            "abcdZ"
+        );
+        assert!(Rucksack::default().is_empty());
+    }
+
+    #[test]
+    fn test_compartments_non_ascii() {
+        // Multi-byte items must be split by item count, not byte length
+        let alphabet = Alphabet::new("aé秘");
+        let (left, right) = Rucksack::compartments("a秘éé", &alphabet);
+
+        assert_eq!(left.items(&alphabet).collect::<String>(), "a秘");
+        assert_eq!(right.items(&alphabet).collect::<String>(), "é");
+        assert_eq!(sum_priorities_with_alphabet("秘aé秘", &alphabet), 3);
+    }
+
+    #[test]
+    fn test_validate() {
+        let alphabet = Alphabet::default();
+
+        let report = validate(DEMO_INPUT, 3, &alphabet);
+        assert!(report.is_valid());
+        assert_eq!(report.rucksacks[0].status, Status::Valid('p'));
+        assert_eq!(report.groups[1].lines, vec![4, 5, 6]);
+        assert_eq!(report.groups[1].status, Status::Valid('Z'));
+        assert_eq!(report.to_string(), "");
+
+        let report = validate("abab\nab秘a\nabcd\nacda\nbce", 2, &alphabet);
+        assert!(!report.is_valid());
+        assert_eq!(
+            report.invalid_rucksacks().cloned().collect::<Vec<_>>(),
+            vec![
+                RucksackReport {
+                    line: 1,
+                    status: Status::AmbiguousOverlap(vec!['a', 'b'])
+                },
+                RucksackReport {
+                    line: 2,
+                    status: Status::UnknownItems(vec!['秘'])
+                },
+                RucksackReport {
+                    line: 3,
+                    status: Status::NoOverlap
+                },
+                RucksackReport {
+                    line: 5,
+                    status: Status::OddLength(3)
+                },
+            ]
+        );
+        assert_eq!(
+            report.groups,
+            vec![
+                GroupReport {
+                    lines: vec![1, 2],
+                    status: Status::UnknownItems(vec!['秘'])
+                },
+                GroupReport {
+                    lines: vec![3, 4],
+                    status: Status::AmbiguousOverlap(vec!['a', 'c', 'd'])
+                },
+                GroupReport {
+                    lines: vec![5],
+                    status: Status::IncompleteGroup(1)
+                },
+            ]
+        );
+        assert_eq!(
+            report.to_string(),
+            "Line 1: ambiguous, shares ['a', 'b']
+Line 2: unknown items ['秘']
+Line 3: no shared item
+Line 5: odd length of 3
+Group [1, 2]: unknown items ['秘']
+Group [3, 4]: ambiguous, shares ['a', 'c', 'd']
+Group [5]: incomplete group of 1 lines
+"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Odd-length line")]
+    fn test_sum_odd_length_panic() {
+        let _ = sum_priorities("aba");
     }
 
     #[test]
@@ -87,8 +639,14 @@ FG",
     }
 
     #[test]
-    #[should_panic]
+    #[should_panic(expected = "Unknown char: 秘")]
     fn test_prio_panic() {
-        get_priority('秘');
+        let _ = Rucksack::from_items("a秘", &Alphabet::default());
+    }
+
+    #[test]
+    #[should_panic(expected = "Duplicate item")]
+    fn test_alphabet_panic() {
+        let _ = Alphabet::new("aba");
     }
 }
@@ -1,24 +1,312 @@
-struct Interval {
-    start: usize,
-    end: usize,
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::fmt::{self, Debug, Display, Formatter};
+use std::ops::{Add, Sub};
+use std::str::FromStr;
+
+use itertools::Itertools;
+
+/// Integer types that can be used as [`Interval`] bounds
+pub trait Bound:
+    Copy + Ord + Debug + Display + FromStr + Add<Output = Self> + Sub<Output = Self>
+{
+    const ZERO: Self;
+    const ONE: Self;
+}
+
+macro_rules! impl_bound {
+    ($($t:ty),*) => {
+        $(impl Bound for $t {
+            const ZERO: Self = 0;
+            const ONE: Self = 1;
+        })*
+    };
+}
+
+impl_bound!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+
+/// A closed interval of integers, with `start <= end`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Interval<T: Bound = usize> {
+    start: T,
+    end: T,
 }
 
-impl Interval {
-    const fn contains(&self, other_interval: &Self) -> bool {
+impl<T: Bound> Interval<T> {
+    /// # Panics
+    /// Panics if `start` is greater than `end`
+    #[must_use]
+    pub fn new(start: T, end: T) -> Self {
+        assert!(
+            start <= end,
+            "Interval start {start} is after its end {end}"
+        );
+
+        Self { start, end }
+    }
+
+    #[must_use]
+    pub const fn start(&self) -> T {
+        self.start
+    }
+
+    #[must_use]
+    pub const fn end(&self) -> T {
+        self.end
+    }
+
+    /// Returns the number of integers in the interval, both ends included
+    #[must_use]
+    pub fn len(&self) -> T {
+        self.end - self.start + T::ONE
+    }
+
+    #[must_use]
+    pub fn contains_value(&self, value: T) -> bool {
+        self.start <= value && value <= self.end
+    }
+
+    /// Returns `true` if `other_interval` is fully inside this interval
+    #[must_use]
+    pub fn contains(&self, other_interval: &Self) -> bool {
         self.start <= other_interval.start && other_interval.end <= self.end
     }
 
-    const fn overlap(&self, other_interval: &Self) -> bool {
+    #[must_use]
+    pub fn overlap(&self, other_interval: &Self) -> bool {
         // Smart solution:
         // https://stackoverflow.com/questions/3269434/whats-the-most-efficient-way-to-test-if-two-ranges-overlap
         self.start <= other_interval.end && other_interval.start <= self.end
     }
+
+    /// Returns `true` if the intervals don't overlap but nothing separates them, like `1-2` and `3-4`
+    #[must_use]
+    pub fn is_adjacent(&self, other_interval: &Self) -> bool {
+        // We subtract instead of adding one to the end to avoid overflows
+        (other_interval.start > self.end && other_interval.start - self.end == T::ONE)
+            || (self.start > other_interval.end && self.start - other_interval.end == T::ONE)
+    }
+
+    #[must_use]
+    pub fn intersection(&self, other_interval: &Self) -> Option<Self> {
+        self.overlap(other_interval).then(|| Self {
+            start: self.start.max(other_interval.start),
+            end: self.end.min(other_interval.end),
+        })
+    }
+
+    #[must_use]
+    pub fn union(&self, other_interval: &Self) -> IntervalSet<T> {
+        IntervalSet::from_iter([*self, *other_interval])
+    }
+
+    /// Returns the values of this interval that are not in `other_interval`
+    #[must_use]
+    pub fn difference(&self, other_interval: &Self) -> IntervalSet<T> {
+        let mut result = IntervalSet::new();
+
+        let Some(common) = self.intersection(other_interval) else {
+            result.insert(*self);
+            return result;
+        };
+
+        if self.start < common.start {
+            result.insert(Self::new(self.start, common.start - T::ONE));
+        }
+
+        if common.end < self.end {
+            result.insert(Self::new(common.end + T::ONE, self.end));
+        }
+
+        result
+    }
+}
+
+impl<T: Bound> Display for Interval<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.start, self.end)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseIntervalError {
+    MissingDash(String),
+    InvalidBound(String),
+    StartAfterEnd(String),
+}
+
+impl Display for ParseIntervalError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingDash(input) => write!(f, "Misformed interval, expected a-b: {input:?}"),
+            Self::InvalidBound(input) => write!(f, "Could not parse {input:?} as integer"),
+            Self::StartAfterEnd(input) => write!(f, "Interval start is after its end: {input:?}"),
+        }
+    }
+}
+
+impl std::error::Error for ParseIntervalError {}
+
+impl<T: Bound> FromStr for Interval<T> {
+    type Err = ParseIntervalError;
+
+    /// Parses the `a-b` format
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        // Looking for the dash after the first char keeps negative starts working
+        let dash = input
+            .char_indices()
+            .skip(1)
+            .find(|(_, c)| *c == '-')
+            .map(|(idx, _)| idx)
+            .ok_or_else(|| ParseIntervalError::MissingDash(input.to_string()))?;
+
+        let (left, right) = (&input[..dash], &input[dash + 1..]);
+
+        let parse_bound = |bound: &str| {
+            bound
+                .trim()
+                .parse::<T>()
+                .map_err(|_| ParseIntervalError::InvalidBound(bound.to_string()))
+        };
+
+        let (start, end) = (parse_bound(left)?, parse_bound(right)?);
+
+        if start > end {
+            return Err(ParseIntervalError::StartAfterEnd(input.to_string()));
+        }
+
+        Ok(Self { start, end })
+    }
 }
 
-fn get_intervals(line: &str) -> (Interval, Interval) {
-    let (left, right) = line.split_once(',').expect("Misformed input: {line:?");
+/// A set of integers, stored as sorted, disjoint and non-adjacent [`Interval`]s
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct IntervalSet<T: Bound = usize> {
+    intervals: Vec<Interval<T>>,
+}
+
+impl<T: Bound> IntervalSet<T> {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self { intervals: vec![] }
+    }
+
+    /// Adds an interval to the set, merging it with the ones it overlaps or touches
+    pub fn insert(&mut self, interval: Interval<T>) {
+        // First interval that ends at or after our start minus one
+        let first = self
+            .intervals
+            .partition_point(|i| i.end < interval.start && !i.is_adjacent(&interval));
+        // First interval that starts strictly after our end plus one
+        let last = self
+            .intervals
+            .partition_point(|i| i.start <= interval.end || i.is_adjacent(&interval));
+
+        let merged = self.intervals[first..last]
+            .iter()
+            .fold(interval, |acc, i| Interval {
+                start: acc.start.min(i.start),
+                end: acc.end.max(i.end),
+            });
+
+        self.intervals.splice(first..last, [merged]);
+    }
+
+    #[must_use]
+    pub fn intervals(&self) -> &[Interval<T>] {
+        &self.intervals
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.intervals.is_empty()
+    }
+
+    /// Returns the number of integers in the set
+    #[must_use]
+    pub fn len(&self) -> T {
+        self.intervals
+            .iter()
+            .fold(T::ZERO, |acc, interval| acc + interval.len())
+    }
+
+    #[must_use]
+    pub fn contains_value(&self, value: T) -> bool {
+        let idx = self.intervals.partition_point(|i| i.end < value);
+
+        self.intervals
+            .get(idx)
+            .is_some_and(|i| i.contains_value(value))
+    }
+}
+
+impl<T: Bound> FromIterator<Interval<T>> for IntervalSet<T> {
+    fn from_iter<I: IntoIterator<Item = Interval<T>>>(iter: I) -> Self {
+        let mut result = Self::new();
+
+        for interval in iter {
+            result.insert(interval);
+        }
+
+        result
+    }
+}
+
+impl<T: Bound> Display for IntervalSet<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (idx, interval) in self.intervals.iter().enumerate() {
+            if idx > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{interval}")?;
+        }
+
+        Ok(())
+    }
+}
+
+fn get_intervals(line: &str) -> Vec<Interval> {
+    line.split(',').map(string_to_interval).collect()
+}
 
-    (string_to_interval(left), string_to_interval(right))
+/// How a predicate on two elves is extended to a group of elves
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupRule {
+    /// At least one pair of elves matches
+    AnyPair,
+    /// Every pair of elves matches
+    AllPairs,
+    /// One elf matches with every other one, in that direction
+    OneAgainstAll,
+}
+
+impl GroupRule {
+    /// Applies `predicate` to the group. Groups of fewer than two elves never match.
+    ///
+    /// `predicate(x, y)` can be directed: pair rules accept a pair in either direction, while
+    /// [`Self::OneAgainstAll`] requires `predicate(one, other)` for every other elf.
+    fn matches(self, group: &[Interval], predicate: impl Fn(&Interval, &Interval) -> bool) -> bool {
+        if group.len() < 2 {
+            return false;
+        }
+
+        match self {
+            Self::AnyPair => group
+                .iter()
+                .tuple_combinations()
+                .any(|(x, y)| predicate(x, y) || predicate(y, x)),
+            Self::AllPairs => group
+                .iter()
+                .tuple_combinations()
+                .all(|(x, y)| predicate(x, y) || predicate(y, x)),
+            Self::OneAgainstAll => group.iter().enumerate().any(|(i, x)| {
+                group
+                    .iter()
+                    .enumerate()
+                    .all(|(j, y)| i == j || predicate(x, y))
+            }),
+        }
+    }
 }
 
 /// This function takes an input string `input` and returns the number of
@@ -37,43 +325,268 @@ fn get_intervals(line: &str) -> (Interval, Interval) {
 /// let result = fully_overlapping_sections(input);
 /// assert_eq!(result, 1);
 /// ```
-#[must_use] pub fn fully_overlapping_sections(input: &str) -> usize {
-    input.lines().fold(0, |acc, line| {
-        let (x, y) = get_intervals(line);
-
-        if x.contains(&y) || y.contains(&x) {
-            acc + 1
-        } else {
-            acc
+#[must_use]
+pub fn fully_overlapping_sections(input: &str) -> usize {
+    fully_overlapping_groups(input, GroupRule::AnyPair)
+}
+
+#[must_use]
+pub fn overlapping_sections(input: &str) -> usize {
+    overlapping_groups(input, GroupRule::AnyPair)
+}
+
+/// Returns the number of lines where sections fully overlap, following `rule` when a line holds
+/// more than two elves.
+///
+/// With [`GroupRule::OneAgainstAll`], a line matches when one elf's sections contain all others.
+///
+/// # Examples
+///
+/// ```
+/// use advent_of_code_2022::day_4::{fully_overlapping_groups, GroupRule};
+/// let input = "1-9,2-3,5-6
+/// 1-9,2-3,2-2";
+///
+/// assert_eq!(fully_overlapping_groups(input, GroupRule::AnyPair), 2);
+/// assert_eq!(fully_overlapping_groups(input, GroupRule::OneAgainstAll), 2);
+/// assert_eq!(fully_overlapping_groups(input, GroupRule::AllPairs), 1);
+/// ```
+#[must_use]
+pub fn fully_overlapping_groups(input: &str, rule: GroupRule) -> usize {
+    input
+        .lines()
+        .filter(|line| rule.matches(&get_intervals(line), Interval::contains))
+        .count()
+}
+
+/// Returns the number of lines where sections overlap, following `rule` when a line holds more
+/// than two elves.
+///
+/// # Examples
+///
+/// ```
+/// use advent_of_code_2022::day_4::{overlapping_groups, GroupRule};
+/// let input = "1-4,3-6,6-8";
+///
+/// assert_eq!(overlapping_groups(input, GroupRule::AnyPair), 1);
+/// assert_eq!(overlapping_groups(input, GroupRule::OneAgainstAll), 1);
+/// assert_eq!(overlapping_groups(input, GroupRule::AllPairs), 0);
+/// ```
+#[must_use]
+pub fn overlapping_groups(input: &str, rule: GroupRule) -> usize {
+    input
+        .lines()
+        .filter(|line| rule.matches(&get_intervals(line), Interval::overlap))
+        .count()
+}
+
+/// Returns the number of sections shared by all the elves of each line.
+///
+/// # Examples
+///
+/// ```
+/// use advent_of_code_2022::day_4::overlap_lengths;
+/// let input = "1-4,3-8
+/// 1-2,3-4
+/// 1-9,2-5,4-7";
+/// assert_eq!(overlap_lengths(input), vec![2, 0, 2]);
+/// ```
+#[must_use]
+pub fn overlap_lengths(input: &str) -> Vec<usize> {
+    input
+        .lines()
+        .map(|line| {
+            get_intervals(line)
+                .into_iter()
+                .map(Some)
+                .reduce(|acc, x| acc.zip(x).and_then(|(acc, x)| acc.intersection(&x)))
+                .flatten()
+                .map_or(0, |i| i.len())
+        })
+        .collect()
+}
+
+/// An elf, identified by its line (starting at 1) and its position on the line (starting at 0)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Elf {
+    pub line: usize,
+    pub position: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Assignment {
+    pub elf: Elf,
+    pub sections: Interval,
+}
+
+/// How the sections of two elves relate to each other
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Relation {
+    /// Some sections are shared
+    Overlap,
+    /// The sections of one elf are all assigned to the other one too
+    FullOverlap,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ElfPair {
+    pub first: Elf,
+    pub second: Elf,
+    pub relation: Relation,
+}
+
+/// The result of analysing every assignment of the input as a single set
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssignmentAnalysis {
+    /// Every pair of elves sharing sections, sorted by elves
+    pub pairs: Vec<ElfPair>,
+    /// Sections between the lowest and highest assigned ones that no elf covers
+    pub uncovered: IntervalSet,
+    /// Sections covered by more than `max_elves` elves
+    pub crowded: IntervalSet,
+}
+
+/// Returns every assignment of the input, across all lines
+#[must_use]
+pub fn get_assignments(input: &str) -> Vec<Assignment> {
+    input
+        .lines()
+        .enumerate()
+        .flat_map(|(idx, line)| {
+            get_intervals(line)
+                .into_iter()
+                .enumerate()
+                .map(move |(position, sections)| Assignment {
+                    elf: Elf {
+                        line: idx + 1,
+                        position,
+                    },
+                    sections,
+                })
+        })
+        .collect()
+}
+
+/// Finds every pair of elves whose sections overlap, whatever their line.
+///
+/// This sorts the assignments by start then sweeps them while keeping the ones still open in a
+/// heap, so it runs in `O(n log n + p)` with `p` the number of pairs.
+#[must_use]
+pub fn overlapping_pairs(assignments: &[Assignment]) -> Vec<ElfPair> {
+    let sorted = assignments
+        .iter()
+        .sorted_unstable_by_key(|a| (a.sections.start(), Reverse(a.sections.end())));
+
+    // Min-heap on the end of the assignments that may still overlap the next ones
+    let mut open: BinaryHeap<Reverse<(usize, usize)>> = BinaryHeap::new();
+    let mut seen: Vec<&Assignment> = Vec::with_capacity(assignments.len());
+    let mut pairs = vec![];
+
+    for assignment in sorted {
+        while open
+            .peek()
+            .is_some_and(|Reverse((end, _))| *end < assignment.sections.start())
+        {
+            open.pop();
         }
-    })
+
+        for Reverse((_, idx)) in &open {
+            let other: &Assignment = seen[*idx];
+
+            let relation = if other.sections.contains(&assignment.sections)
+                || assignment.sections.contains(&other.sections)
+            {
+                Relation::FullOverlap
+            } else {
+                Relation::Overlap
+            };
+
+            let (first, second) = if other.elf < assignment.elf {
+                (other.elf, assignment.elf)
+            } else {
+                (assignment.elf, other.elf)
+            };
+
+            pairs.push(ElfPair {
+                first,
+                second,
+                relation,
+            });
+        }
+
+        open.push(Reverse((assignment.sections.end(), seen.len())));
+        seen.push(assignment);
+    }
+
+    pairs.sort_unstable_by_key(|p| (p.first, p.second));
+
+    pairs
 }
 
-#[must_use] pub fn overlapping_sections(input: &str) -> usize {
-    input.lines().fold(0, |acc, line| {
-        let (x, y) = get_intervals(line);
+/// Returns the sections covered by exactly `count` elves and the ones covered by more, in the
+/// `[lowest assigned section, highest assigned section]` range.
+fn coverage(assignments: &[Assignment], count: usize) -> (IntervalSet, IntervalSet) {
+    // +1 when an assignment starts, -1 right after it ends
+    let events = assignments
+        .iter()
+        .flat_map(|a| [(a.sections.start(), 1), (a.sections.end() + 1, -1)])
+        .sorted_unstable();
 
-        if x.overlap(&y) {
-            acc + 1
-        } else {
-            acc
+    let mut exactly = IntervalSet::new();
+    let mut more = IntervalSet::new();
+    let mut current: isize = 0;
+    let mut previous: Option<usize> = None;
+
+    for (section, delta) in events {
+        if let Some(start) = previous.filter(|start| *start < section) {
+            let segment = Interval::new(start, section - 1);
+
+            match current.unsigned_abs().cmp(&count) {
+                Ordering::Equal => exactly.insert(segment),
+                Ordering::Greater => more.insert(segment),
+                Ordering::Less => {}
+            }
         }
-    })
+
+        current += delta;
+        previous = Some(section);
+    }
+
+    (exactly, more)
 }
 
-fn string_to_interval(input: &str) -> Interval {
-    let (left, right) = input.split_once('-').expect("Misformed input: {input:?}");
+/// Analyses all the assignments of the input as one set.
+///
+/// # Examples
+///
+/// ```
+/// use advent_of_code_2022::day_4::analyse_assignments;
+/// let input = "1-4,3-5
+/// 8-9,2-3";
+/// let analysis = analyse_assignments(input, 2);
+///
+/// assert_eq!(analysis.pairs.len(), 3);
+/// assert_eq!(analysis.uncovered.to_string(), "6-7");
+/// assert_eq!(analysis.crowded.to_string(), "3-3");
+/// ```
+#[must_use]
+pub fn analyse_assignments(input: &str, max_elves: usize) -> AssignmentAnalysis {
+    let assignments = get_assignments(input);
+
+    let (uncovered, _) = coverage(&assignments, 0);
+    let (_, crowded) = coverage(&assignments, max_elves);
 
-    Interval {
-        start: left
-            .parse::<usize>()
-            .expect("Could not parse {left:?} as integer"),
-        end: right
-            .parse::<usize>()
-            .expect("Could not parse {right:?} as integer"),
+    AssignmentAnalysis {
+        pairs: overlapping_pairs(&assignments),
+        uncovered,
+        crowded,
     }
 }
 
+fn string_to_interval(input: &str) -> Interval {
+    input.parse().unwrap_or_else(|err| panic!("{err}"))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -103,4 +616,189 @@ mod tests {
         assert_eq!(overlapping_sections("2-6,4-8"), 1);
         assert_eq!(overlapping_sections("2-8,3-7"), 1);
     }
+
+    #[test]
+    fn test_group_rules() {
+        let input = "1-2,3-4,5-6
+1-2,2-3,3-4
+2-3,1-4,3-4
+1-5,2-4,3-3
+3-3,1-4,2-5
+4-4";
+
+        assert_eq!(overlapping_groups(input, GroupRule::AnyPair), 4);
+        assert_eq!(overlapping_groups(input, GroupRule::OneAgainstAll), 4);
+        assert_eq!(overlapping_groups(input, GroupRule::AllPairs), 3);
+
+        // No single elf contains both others on the 3-3,1-4,2-5 line
+        assert_eq!(
+            fully_overlapping_groups("3-3,1-4,2-5", GroupRule::OneAgainstAll),
+            0
+        );
+        assert_eq!(fully_overlapping_groups(input, GroupRule::AnyPair), 3);
+        assert_eq!(fully_overlapping_groups(input, GroupRule::OneAgainstAll), 2);
+        assert_eq!(fully_overlapping_groups(input, GroupRule::AllPairs), 1);
+
+        // All rules are the same for pairs
+        for rule in [
+            GroupRule::AnyPair,
+            GroupRule::AllPairs,
+            GroupRule::OneAgainstAll,
+        ] {
+            assert_eq!(fully_overlapping_groups(DEMO_INPUT, rule), 2);
+            assert_eq!(overlapping_groups(DEMO_INPUT, rule), 4);
+        }
+    }
+
+    #[test]
+    fn test_group_assignments() {
+        let assignments = get_assignments("1-2,3-4,5-6");
+
+        assert_eq!(assignments.len(), 3);
+        assert_eq!(
+            assignments[2].elf,
+            Elf {
+                line: 1,
+                position: 2
+            }
+        );
+        assert_eq!(assignments[2].sections, Interval::new(5, 6));
+    }
+
+    #[test]
+    fn test_overlap_lengths() {
+        assert_eq!(overlap_lengths(DEMO_INPUT), vec![0, 0, 1, 5, 1, 3]);
+    }
+
+    #[test]
+    fn test_parse_interval() {
+        assert_eq!("2-4".parse(), Ok(Interval::new(2_usize, 4)));
+        assert_eq!("-5--2".parse(), Ok(Interval::new(-5_i32, -2)));
+        assert_eq!(
+            "2,4".parse::<Interval>(),
+            Err(ParseIntervalError::MissingDash("2,4".to_string()))
+        );
+        assert_eq!(
+            "a-4".parse::<Interval>(),
+            Err(ParseIntervalError::InvalidBound("a".to_string()))
+        );
+        assert_eq!(
+            "4-2".parse::<Interval>(),
+            Err(ParseIntervalError::StartAfterEnd("4-2".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_interval_algebra() {
+        let x = Interval::new(2, 6);
+        let y = Interval::new(5, 9);
+
+        assert_eq!(x.len(), 5);
+        assert_eq!(x.intersection(&y), Some(Interval::new(5, 6)));
+        assert_eq!(x.intersection(&Interval::new(7, 9)), None);
+        assert_eq!(x.union(&y).intervals(), &[Interval::new(2, 9)]);
+        assert_eq!(x.difference(&y).intervals(), &[Interval::new(2, 4)]);
+        assert_eq!(
+            x.difference(&Interval::new(3, 4)).intervals(),
+            &[Interval::new(2, 2), Interval::new(5, 6)]
+        );
+        assert!(x.difference(&Interval::new(0, 10)).is_empty());
+
+        assert!(x.is_adjacent(&Interval::new(7, 9)));
+        assert!(Interval::new(7, 9).is_adjacent(&x));
+        assert!(!x.is_adjacent(&y));
+        assert!(!x.is_adjacent(&Interval::new(8, 9)));
+        assert!(Interval::new(0_u8, 254).is_adjacent(&Interval::new(255, 255)));
+    }
+
+    #[test]
+    fn test_interval_set() {
+        let mut set: IntervalSet = [Interval::new(10, 12), Interval::new(1, 2)]
+            .into_iter()
+            .collect();
+
+        assert_eq!(set.to_string(), "1-2,10-12");
+        assert_eq!(set.len(), 5);
+
+        set.insert(Interval::new(3, 4));
+        assert_eq!(set.to_string(), "1-4,10-12");
+
+        set.insert(Interval::new(6, 7));
+        assert_eq!(set.to_string(), "1-4,6-7,10-12");
+
+        set.insert(Interval::new(5, 9));
+        assert_eq!(set.to_string(), "1-12");
+        assert!(set.contains_value(12));
+        assert!(!set.contains_value(13));
+        assert_eq!(IntervalSet::<usize>::new().len(), 0);
+    }
+
+    #[test]
+    fn test_overlapping_pairs() {
+        let elf = |line, position| Elf { line, position };
+
+        let pairs = overlapping_pairs(&get_assignments(DEMO_INPUT));
+
+        // The 4 pairs of the second part are found...
+        for line in [3, 4, 5, 6] {
+            assert!(pairs
+                .iter()
+                .any(|p| p.first == elf(line, 0) && p.second == elf(line, 1)));
+        }
+
+        // ... along with the ones across lines
+        assert!(pairs.contains(&ElfPair {
+            first: elf(1, 0),
+            second: elf(2, 0),
+            relation: Relation::FullOverlap
+        }));
+        assert!(pairs.contains(&ElfPair {
+            first: elf(1, 1),
+            second: elf(3, 0),
+            relation: Relation::Overlap
+        }));
+        assert!(!pairs
+            .iter()
+            .any(|p| p.first == elf(1, 0) && p.second == elf(1, 1)));
+    }
+
+    #[test]
+    fn test_overlapping_pairs_bruteforce() {
+        let assignments = get_assignments(DEMO_INPUT);
+
+        let mut expected = vec![];
+        for (i, x) in assignments.iter().enumerate() {
+            for y in &assignments[i + 1..] {
+                if x.sections.overlap(&y.sections) {
+                    expected.push((x.elf, y.elf));
+                }
+            }
+        }
+
+        let pairs: Vec<(Elf, Elf)> = overlapping_pairs(&assignments)
+            .iter()
+            .map(|p| (p.first, p.second))
+            .collect();
+
+        assert_eq!(pairs, expected);
+    }
+
+    #[test]
+    fn test_analyse_assignments() {
+        let analysis = analyse_assignments(DEMO_INPUT, 4);
+
+        assert!(analysis.uncovered.is_empty());
+        // Sections 3 to 7 are each assigned to at least 5 elves
+        assert_eq!(analysis.crowded.to_string(), "3-7");
+
+        let analysis = analyse_assignments("1-2,8-9\n5-5,5-5", 1);
+        assert_eq!(analysis.uncovered.to_string(), "3-4,6-7");
+        assert_eq!(analysis.crowded.to_string(), "5-5");
+    }
+
+    #[test]
+    #[should_panic(expected = "Could not parse")]
+    fn test_parse_panic() {
+        let _ = fully_overlapping_sections("2-x,1-4");
+    }
 }
@@ -1,32 +1,439 @@
 use std::collections::HashMap;
+use std::fmt::{self, Debug, Display, Formatter};
+use std::ops::Range;
 
 use itertools::Itertools;
 use nom::{
-    branch::alt,
-    bytes::complete::tag,
-    character::complete::satisfy,
-    combinator::{map, value},
-    multi::separated_list1,
-    sequence::delimited,
+    bytes::complete::{is_not, tag},
+    character::complete::{digit1, space0},
+    combinator::{all_consuming, map, map_res},
+    sequence::{delimited, preceded, tuple},
     IResult,
 };
 
+/// A crane model, which decides how picked crates land on the destination stack.
+///
+/// Cranes always take the top crates of the origin stack and put them on top of the destination
+/// stack, so a model is a reordering of the picked crates.
+pub trait Crane: Debug {
+    /// Returns, for each crate landing on the destination from bottom to top, its index in the
+    /// picked crates taken from bottom to top as they were on the origin stack.
+    ///
+    /// The result must be a permutation of `0..crates_count`.
+    fn arrange(&self, crates_count: usize) -> Vec<usize>;
+
+    /// Returns what a move costs, nothing by default
+    fn cost(&self, _movement: &MovementInfo) -> usize {
+        0
+    }
+
+    /// Returns the name of the crane, as understood by [`crane_from_name`]
+    fn name(&self) -> String;
+}
+
+macro_rules! impl_crane_for_pointer {
+    ($($pointer:ty),*) => {
+        $(impl<C: Crane + ?Sized> Crane for $pointer {
+            fn arrange(&self, crates_count: usize) -> Vec<usize> {
+                (**self).arrange(crates_count)
+            }
+
+            fn cost(&self, movement: &MovementInfo) -> usize {
+                (**self).cost(movement)
+            }
+
+            fn name(&self) -> String {
+                (**self).name()
+            }
+        })*
+    };
+}
+
+impl_crane_for_pointer!(Box<C>, &C);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CrateMoverVersion {
+    /// The CrateMover 9000 moves crates one at a time
     V1,
+    /// The CrateMover 9001 moves all crates at once
     V2,
 }
 
+impl Crane for CrateMoverVersion {
+    fn arrange(&self, crates_count: usize) -> Vec<usize> {
+        match self {
+            // Crates are moved one at a time, so they end up reversed
+            Self::V1 => (0..crates_count).rev().collect(),
+            Self::V2 => (0..crates_count).collect(),
+        }
+    }
+
+    fn name(&self) -> String {
+        match self {
+            Self::V1 => "v1".to_string(),
+            Self::V2 => "v2".to_string(),
+        }
+    }
+}
+
+/// A crane that lifts at most `max_lift` crates at once, splitting bigger moves in several lifts
+/// that each keep their crates in order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MaxLiftCrane {
+    max_lift: usize,
+}
+
+impl MaxLiftCrane {
+    /// # Panics
+    /// Panics if `max_lift` is 0
+    #[must_use]
+    pub fn new(max_lift: usize) -> Self {
+        assert!(max_lift > 0, "A crane must lift at least one crate");
+
+        Self { max_lift }
+    }
+}
+
+impl Crane for MaxLiftCrane {
+    fn arrange(&self, crates_count: usize) -> Vec<usize> {
+        // The first lift takes the top crates, so it ends up at the bottom of the destination
+        let indices: Vec<usize> = (0..crates_count).collect();
+
+        indices.rchunks(self.max_lift).flatten().copied().collect()
+    }
+
+    fn name(&self) -> String {
+        format!("max-lift-{}", self.max_lift)
+    }
+}
+
+/// A crane that moves all crates at once, then rotates them by `shift` positions towards the
+/// bottom of the destination stack
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RotatingCrane {
+    shift: usize,
+}
+
+impl RotatingCrane {
+    #[must_use]
+    pub const fn new(shift: usize) -> Self {
+        Self { shift }
+    }
+}
+
+impl Crane for RotatingCrane {
+    fn arrange(&self, crates_count: usize) -> Vec<usize> {
+        let mut indices: Vec<usize> = (0..crates_count).collect();
+
+        if crates_count > 0 {
+            indices.rotate_left(self.shift % crates_count);
+        }
+
+        indices
+    }
+
+    fn name(&self) -> String {
+        format!("rotating-{}", self.shift)
+    }
+}
+
+/// A crane that behaves like `inner` but charges `cost_per_crate` for each moved crate
+#[derive(Debug)]
+pub struct MeteredCrane<C: Crane> {
+    inner: C,
+    cost_per_crate: usize,
+}
+
+impl<C: Crane> MeteredCrane<C> {
+    #[must_use]
+    pub const fn new(inner: C, cost_per_crate: usize) -> Self {
+        Self {
+            inner,
+            cost_per_crate,
+        }
+    }
+}
+
+impl<C: Crane> Crane for MeteredCrane<C> {
+    fn arrange(&self, crates_count: usize) -> Vec<usize> {
+        self.inner.arrange(crates_count)
+    }
+
+    fn cost(&self, movement: &MovementInfo) -> usize {
+        self.inner.cost(movement) + self.cost_per_crate * movement.crates_count
+    }
+
+    fn name(&self) -> String {
+        format!("metered-{}-{}", self.cost_per_crate, self.inner.name())
+    }
+}
+
+/// Builds a crane from its name.
+///
+/// Known names are `v1`, `v2`, `max-lift-N`, `rotating-N` and `metered-N-<crane>`, with `N` an
+/// integer.
+///
+/// # Examples
+///
+/// ```
+/// use advent_of_code_2022::day_5::crane_from_name;
+///
+/// let crane = crane_from_name("metered-3-max-lift-2").unwrap();
+/// assert_eq!(crane.arrange(5), vec![3, 4, 1, 2, 0]);
+/// assert_eq!(crane.name(), "metered-3-max-lift-2");
+/// assert!(crane_from_name("max-lift-0").is_none());
+/// ```
+#[must_use]
+pub fn crane_from_name(name: &str) -> Option<Box<dyn Crane>> {
+    match name {
+        "v1" => return Some(Box::new(CrateMoverVersion::V1)),
+        "v2" => return Some(Box::new(CrateMoverVersion::V2)),
+        _ => {}
+    }
+
+    if let Some(max_lift) = name.strip_prefix("max-lift-") {
+        let max_lift = max_lift.parse().ok().filter(|m| *m > 0)?;
+        return Some(Box::new(MaxLiftCrane::new(max_lift)));
+    }
+
+    if let Some(shift) = name.strip_prefix("rotating-") {
+        return Some(Box::new(RotatingCrane::new(shift.parse().ok()?)));
+    }
+
+    if let Some(rest) = name.strip_prefix("metered-") {
+        let (cost_per_crate, inner) = rest.split_once('-')?;
+
+        return Some(Box::new(MeteredCrane::new(
+            crane_from_name(inner)?,
+            cost_per_crate.parse().ok()?,
+        )));
+    }
+
+    None
+}
+
+/// A move that was applied to the yard, with the crates it moved from bottom to top as they were
+/// on the origin stack
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+    pub movement: MovementInfo,
+    pub crates: Vec<String>,
+    pub cost: usize,
+}
+
+/// The state of the crate yard, moved step by step through a list of moves
+#[derive(Debug)]
+pub struct CrateYard<'a> {
+    positions: HashMap<usize, Vec<String>>,
+    crane: Box<dyn Crane + 'a>,
+    moves: Vec<MovementInfo>,
+    // Steps of moves[..history.len()], which are the ones currently applied
+    history: Vec<Step>,
+    // Line of the first move, used to name moves in errors
+    first_move_line: usize,
+}
+
+impl<'a> CrateYard<'a> {
+    /// Builds a yard with no move applied yet, moves being numbered from 1 in errors
+    ///
+    /// # Errors
+    /// Returns an error if a move cannot be applied
+    pub fn new(
+        positions: HashMap<usize, Vec<String>>,
+        moves: Vec<MovementInfo>,
+        crane: impl Crane + 'a,
+    ) -> Result<Self, MoveError> {
+        Self::with_first_move_line(positions, moves, Box::new(crane), 1)
+    }
+
+    fn with_first_move_line(
+        positions: HashMap<usize, Vec<String>>,
+        moves: Vec<MovementInfo>,
+        crane: Box<dyn Crane + 'a>,
+        first_move_line: usize,
+    ) -> Result<Self, MoveError> {
+        validate_moves(&positions, &moves, first_move_line)?;
+
+        Ok(Self {
+            positions,
+            crane,
+            moves,
+            history: vec![],
+            first_move_line,
+        })
+    }
+
+    /// Builds a yard from the puzzle input, with no move applied yet
+    ///
+    /// # Errors
+    /// Returns an error naming the input line of the first move that cannot be parsed or applied
+    ///
+    /// # Panics
+    /// Panics if the drawing is misformed
+    pub fn from_input(input: &str, crane: impl Crane + 'a) -> Result<Self, MoveError> {
+        let (drawing, moves) = input.split_once("\n\n").expect("No actions founds");
+
+        // Moves start after the drawing and the blank line
+        let first_move_line = drawing.lines().count() + 2;
+
+        Self::with_first_move_line(
+            read_starting_position(drawing),
+            parse_movements(moves, first_move_line)?,
+            Box::new(crane),
+            first_move_line,
+        )
+    }
+
+    #[must_use]
+    pub const fn positions(&self) -> &HashMap<usize, Vec<String>> {
+        &self.positions
+    }
+
+    #[must_use]
+    pub fn moves(&self) -> &[MovementInfo] {
+        &self.moves
+    }
+
+    /// Returns the steps applied so far, in order
+    #[must_use]
+    pub fn history(&self) -> &[Step] {
+        &self.history
+    }
+
+    #[must_use]
+    pub fn crane(&self) -> &dyn Crane {
+        self.crane.as_ref()
+    }
+
+    /// Returns the total cost of the moves applied so far
+    #[must_use]
+    pub fn total_cost(&self) -> usize {
+        self.history.iter().map(|step| step.cost).sum()
+    }
+
+    /// Returns the number of moves applied so far
+    #[must_use]
+    pub fn current_move(&self) -> usize {
+        self.history.len()
+    }
+
+    /// Returns the top crate of each column, in column order, with a space for empty columns
+    #[must_use]
+    pub fn top_crates(&self) -> String {
+        get_top_crates(&self.positions)
+    }
+
+    /// Draws the current stacks in the puzzle format
+    #[must_use]
+    pub fn draw(&self) -> String {
+        draw_positions(&self.positions)
+    }
+
+    /// Applies the next move, returning `None` if all moves were already applied
+    pub fn step(&mut self) -> Option<&Step> {
+        let movement = *self.moves.get(self.history.len())?;
+
+        // Moves are validated when they are added, so this cannot fail
+        let crates = apply_movement(&mut self.positions, &movement, self.crane.as_ref());
+        let cost = self.crane.cost(&movement);
+
+        self.history.push(Step {
+            movement,
+            crates,
+            cost,
+        });
+        self.history.last()
+    }
+
+    /// Reverts the last applied move, returning `None` if no move was applied.
+    ///
+    /// The move stays in the move list, so it can be applied again with [`CrateYard::redo`].
+    pub fn undo(&mut self) -> Option<Step> {
+        let step = self.history.pop()?;
+
+        let to = self
+            .positions
+            .get_mut(&step.movement.to)
+            .expect("Undoing a move to a column that does not exist");
+        to.truncate(to.len() - step.crates.len());
+
+        self.positions
+            .get_mut(&step.movement.from)
+            .expect("Undoing a move from a column that does not exist")
+            .extend(step.crates.iter().cloned());
+
+        Some(step)
+    }
+
+    /// Applies again the last undone move
+    pub fn redo(&mut self) -> Option<&Step> {
+        self.step()
+    }
+
+    /// Adds a move after the current one and applies it, dropping the moves that were undone
+    ///
+    /// # Errors
+    /// Returns an error if the move cannot be applied, in which case the yard is left untouched
+    pub fn push_move(&mut self, movement: MovementInfo) -> Result<&Step, MoveError> {
+        check_movement(
+            &self.positions,
+            &movement,
+            self.first_move_line + self.history.len(),
+        )?;
+
+        self.moves.truncate(self.history.len());
+        self.moves.push(movement);
+
+        Ok(self.step().expect("The pushed move is the next one"))
+    }
+
+    /// Undoes or applies moves until exactly `move_count` moves are applied
+    ///
+    /// # Panics
+    /// Panics if there are fewer than `move_count` moves
+    pub fn jump_to(&mut self, move_count: usize) {
+        assert!(
+            move_count <= self.moves.len(),
+            "Cannot jump to move {move_count}, there are only {} moves",
+            self.moves.len()
+        );
+
+        while self.history.len() > move_count {
+            self.undo();
+        }
+
+        while self.history.len() < move_count {
+            self.step();
+        }
+    }
+
+    /// Applies every remaining move
+    pub fn run(&mut self) {
+        self.jump_to(self.moves.len());
+    }
+}
+
+impl Display for CrateYard<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.draw())
+    }
+}
+
 /// Finds the top crates after performing the given actions on them.
 ///
 /// # Arguments
 ///
 /// * `input`: a string representing the starting positions and actions to be performed on the crates.
-/// * `version`: the version of the crate mover to use.
+/// * `crane`: the crane to use, for example a version of the crate mover.
 ///
 /// # Returns
 ///
 /// A string containing a comma-separated list of the top crates after all the actions have been performed.
 ///
+/// # Panics
+///
+/// Panics with the offending line if a move cannot be parsed or applied.
+///
 /// # Examples
 /// ```
 /// use advent_of_code_2022::day_5::find_top_crates;
@@ -41,143 +448,507 @@ pub enum CrateMoverVersion {
 /// assert_eq!(find_top_crates(input, &CrateMoverVersion::V1), "CM");
 /// ```
 #[must_use]
-pub fn find_top_crates(input: &str, version: &CrateMoverVersion) -> String {
-    let mut data = input.split("\n\n");
+pub fn find_top_crates(input: &str, crane: &dyn Crane) -> String {
+    let mut yard = crane_yard(input, crane);
 
-    // This gets the starting block
-    let mut positions = read_starting_position(data.next().expect("Empty input string"));
+    yard.run();
 
-    // We then iterate on the rows
-    for row in data.next().expect("No actions founds").lines() {
-        match version {
-            CrateMoverVersion::V1 => move_crates(&mut positions, row),
-            CrateMoverVersion::V2 => move_crates_v2(&mut positions, row),
-        }
-    }
+    yard.top_crates()
+}
 
-    get_top_crates(&positions)
+/// Returns the top crates and the total cost after performing all moves with `crane`
+///
+/// # Panics
+///
+/// Panics with the offending line if a move cannot be parsed or applied.
+#[must_use]
+pub fn find_top_crates_and_cost(input: &str, crane: &dyn Crane) -> (String, usize) {
+    let mut yard = crane_yard(input, crane);
+
+    yard.run();
+
+    (yard.top_crates(), yard.total_cost())
 }
 
-fn read_starting_position(input: &str) -> HashMap<usize, Vec<char>> {
-    let (_, rows) = crate_rows(input).expect("Cannot parse rows: {input}");
+fn crane_yard<'a>(input: &str, crane: &'a dyn Crane) -> CrateYard<'a> {
+    CrateYard::from_input(input, crane).unwrap_or_else(|err| panic!("{err}"))
+}
+
+/// Parses the drawing of the stacks into column number -> crates from bottom to top.
+///
+/// Column positions come from the numbered base line, so columns can be wider than 3 characters
+/// and crate labels can be any token without brackets or whitespace. Each crate goes to the
+/// column whose number it sits above, or to the closest one.
+///
+/// Columns listed on the numbered base line exist even when they hold no crate.
+///
+/// # Panics
+/// Panics if the drawing is misformed
+#[must_use]
+pub fn read_starting_position(input: &str) -> HashMap<usize, Vec<String>> {
+    let mut lines = input
+        .lines()
+        .rev()
+        .skip_while(|line| line.trim().is_empty());
+
+    let base_line = lines.next().expect("Empty drawing");
+    let columns = match all_consuming(column_numbers)(base_line.trim_end()) {
+        Ok((_, columns)) if !columns.is_empty() => columns,
+        _ => panic!("Cannot parse base line: {base_line:?}"),
+    };
 
-    let mut result = HashMap::new();
+    let mut result: HashMap<usize, Vec<String>> = columns
+        .iter()
+        .map(|(_, column)| (*column, vec![]))
+        .collect();
 
-    for row in rows.iter().rev() {
-        for (idx, char) in row.iter().enumerate() {
-            if let Some(c) = char {
-                let column: &mut Vec<char> = result.entry(idx + 1).or_default();
-                column.push(*c);
-            }
+    // Lines are already reversed, so we read from the bottom row up
+    for row in lines {
+        let crates = match all_consuming(crate_row)(row.trim_end()) {
+            Ok((_, crates)) => crates,
+            Err(err) => panic!("Cannot parse row {row:?}: {err}"),
+        };
+
+        for (span, label) in crates {
+            let column = closest_column(&columns, &span);
+
+            result
+                .get_mut(&column)
+                .expect("Columns come from the base line")
+                .push(label.to_string());
         }
     }
 
     result
 }
 
-/// Nom parser to parse "[A]" -> 'A'
-fn crate_label(s: &str) -> IResult<&str, char> {
-    let crate_char = satisfy(|c| c.is_ascii_uppercase());
-    delimited(tag("["), crate_char, tag("]"))(s)
+/// Returns the column whose number overlaps `span`, or the one with the closest center
+fn closest_column(columns: &[(Range<usize>, usize)], span: &Range<usize>) -> usize {
+    let center = |range: &Range<usize>| range.start + range.end;
+
+    columns
+        .iter()
+        .min_by_key(|(number_span, _)| {
+            let overlaps = number_span.start < span.end && span.start < number_span.end;
+
+            (!overlaps, center(number_span).abs_diff(center(span)))
+        })
+        .map(|(_, column)| *column)
+        .expect("There is at least one column")
 }
 
-/// Nom parser to parse "[A]     [B] [C]" -> [Some('A'), None, Some('B'), Some('C')]
-fn crate_row(s: &str) -> IResult<&str, Vec<Option<char>>> {
-    let maybe_crate = map(crate_label, Some);
-    let empty_space = value(None, tag("   "));
-    let crate_or_empty = alt((maybe_crate, empty_space));
-    separated_list1(tag(" "), crate_or_empty)(s)
+/// Draws the stacks in the puzzle format, the inverse of [`read_starting_position`].
+///
+/// Every row is padded to the full width and columns missing from `positions` are drawn empty.
+/// Columns are widened to fit the longest label and the biggest column number.
+///
+/// # Examples
+///
+/// ```
+/// use std::collections::HashMap;
+/// use advent_of_code_2022::day_5::{draw_positions, read_starting_position};
+///
+/// let positions = HashMap::from([
+///     (1, vec!["Z".to_string(), "N".to_string()]),
+///     (2, vec!["M".to_string(), "C".to_string(), "D".to_string()]),
+///     (3, vec!["P".to_string()]),
+/// ]);
+/// let drawing = draw_positions(&positions);
+///
+/// assert_eq!(
+///     drawing,
+///     "    [D]    \n[N] [C]    \n[Z] [M] [P]\n 1   2   3 "
+/// );
+/// assert_eq!(read_starting_position(&drawing), positions);
+/// ```
+#[must_use]
+pub fn draw_positions(positions: &HashMap<usize, Vec<String>>) -> String {
+    let columns = positions.keys().max().copied().unwrap_or_default();
+    let height = positions.values().map(Vec::len).max().unwrap_or_default();
+
+    // A column fits its brackets and its number
+    let width = positions
+        .values()
+        .flatten()
+        .map(|label| label.chars().count() + 2)
+        .chain([3, columns.to_string().len()])
+        .max()
+        .unwrap_or_default();
+
+    let mut lines = vec![];
+
+    // We draw from the top row down to the bottom one
+    for level in (0..height).rev() {
+        let row = (1..=columns)
+            .map(|column| {
+                positions
+                    .get(&column)
+                    .and_then(|stack| stack.get(level))
+                    .map_or_else(
+                        || " ".repeat(width),
+                        |c| format!("{:^width$}", format!("[{c}]")),
+                    )
+            })
+            .join(" ");
+
+        lines.push(row);
+    }
+
+    lines.push(
+        (1..=columns)
+            .map(|column| format!("{column:^width$}"))
+            .join(" "),
+    );
+
+    lines.join("\n")
 }
 
-/// Nom parser to parse multiple newline-separated rows of crates into a list
-/// of rows, specified by `crate_row`.
-fn crate_rows(s: &str) -> IResult<&str, Vec<Vec<Option<char>>>> {
-    separated_list1(tag("\n"), crate_row)(s)
+/// Nom parser to parse "[AB]" -> "AB"
+fn crate_label(s: &str) -> IResult<&str, &str> {
+    delimited(tag("["), is_not("[] \t\n"), tag("]"))(s)
 }
 
-struct MovementInfo {
-    crates_count: usize,
-    from: usize,
-    to: usize,
+/// Nom parser to parse "[A]     [BC]" -> [(0..3, "A"), (8..12, "BC")], with the span of each
+/// crate in the row
+fn crate_row(row: &str) -> IResult<&str, Vec<(Range<usize>, &str)>> {
+    positioned(row, crate_label)
 }
 
-fn get_movement_info(row: &str) -> MovementInfo {
-    let mut lines = row.split(' ');
+/// Nom parser to parse " 1   2  10" -> [(1..2, 1), (5..6, 2), (8..10, 10)], with the span of
+/// each column number in the row
+fn column_numbers(row: &str) -> IResult<&str, Vec<(Range<usize>, usize)>> {
+    positioned(row, map_res(digit1, str::parse::<usize>))
+}
 
-    lines.next();
+/// Applies `parser` as many times as possible on space-separated items of `row`, keeping the span
+/// of each item
+fn positioned<'a, O>(
+    row: &'a str,
+    mut parser: impl FnMut(&'a str) -> IResult<&'a str, O>,
+) -> IResult<&'a str, Vec<(Range<usize>, O)>> {
+    let mut result = vec![];
+    let mut rest = row;
 
-    // TODO Fix all the unwraps with a proper Result return type!
-    let crates_count: usize = lines.next().unwrap().parse().unwrap();
+    loop {
+        let (item_start, _) = space0(rest)?;
 
-    lines.next();
+        let Ok((item_end, item)) = parser(item_start) else {
+            return Ok((rest, result));
+        };
 
-    let from: usize = lines.next().unwrap().parse().unwrap();
+        result.push((
+            row.len() - item_start.len()..row.len() - item_end.len(),
+            item,
+        ));
+        rest = item_end;
+    }
+}
 
-    lines.next();
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MovementInfo {
+    pub crates_count: usize,
+    pub from: usize,
+    pub to: usize,
+}
 
-    let to: usize = lines.next().unwrap().parse().unwrap();
+/// An error on a move, with the line of the move in the input (starting at 1)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MoveError {
+    /// The line is not in the `move N from A to B` format
+    Misformed { line: usize, row: String },
+    /// Crates are taken from a column that does not exist
+    MissingOrigin { line: usize, column: usize },
+    /// Crates are put on a column that does not exist
+    MissingDestination { line: usize, column: usize },
+    /// More crates are taken than the column holds
+    NotEnoughCrates {
+        line: usize,
+        column: usize,
+        crates_count: usize,
+        height: usize,
+    },
+}
 
-    MovementInfo {
-        crates_count,
-        from,
-        to,
+impl Display for MoveError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Misformed { line, row } => {
+                write!(f, "Line {line}: expected \"move N from A to B\", got {row:?}")
+            }
+            Self::MissingOrigin { line, column } => {
+                write!(f, "Line {line}: cannot take crates from missing column {column}")
+            }
+            Self::MissingDestination { line, column } => {
+                write!(f, "Line {line}: cannot put crates on missing column {column}")
+            }
+            Self::NotEnoughCrates {
+                line,
+                column,
+                crates_count,
+                height,
+            } => write!(
+                f,
+                "Line {line}: cannot take {crates_count} crates from column {column}, which holds {height}"
+            ),
+        }
     }
 }
 
-fn move_crates(positions: &mut HashMap<usize, Vec<char>>, row: &str) {
-    let movement_info = get_movement_info(row);
+impl std::error::Error for MoveError {}
 
-    let mut moving_crates = vec![];
+/// Nom parser to parse "move 1 from 2 to 3" -> `MovementInfo`
+fn movement_info(s: &str) -> IResult<&str, MovementInfo> {
+    let number = || map_res(digit1, str::parse::<usize>);
 
-    for _ in 0..movement_info.crates_count {
-        moving_crates.push(
-            positions
-                .get_mut(&(movement_info.from))
-                .unwrap()
-                .pop()
-                .unwrap(),
-        );
+    map(
+        tuple((
+            preceded(tag("move "), number()),
+            preceded(tag(" from "), number()),
+            preceded(tag(" to "), number()),
+        )),
+        |(crates_count, from, to)| MovementInfo {
+            crates_count,
+            from,
+            to,
+        },
+    )(s)
+}
+
+/// Parses one move per line, `first_line` being the line number of the first move
+///
+/// # Errors
+/// Returns an error naming the first line that is not a valid move
+pub fn parse_movements(input: &str, first_line: usize) -> Result<Vec<MovementInfo>, MoveError> {
+    input
+        .lines()
+        .enumerate()
+        .map(
+            |(idx, row)| match all_consuming(movement_info)(row.trim_end()) {
+                Ok((_, movement)) => Ok(movement),
+                Err(_) => Err(MoveError::Misformed {
+                    line: first_line + idx,
+                    row: row.to_string(),
+                }),
+            },
+        )
+        .collect()
+}
+
+fn check_movement(
+    positions: &HashMap<usize, Vec<String>>,
+    movement: &MovementInfo,
+    line: usize,
+) -> Result<(), MoveError> {
+    check_heights(
+        |column| positions.get(&column).map(Vec::len),
+        movement,
+        line,
+    )
+}
+
+/// Checks a move against the column heights given by `height_of`
+fn check_heights(
+    height_of: impl Fn(usize) -> Option<usize>,
+    movement: &MovementInfo,
+    line: usize,
+) -> Result<(), MoveError> {
+    let height = height_of(movement.from).ok_or(MoveError::MissingOrigin {
+        line,
+        column: movement.from,
+    })?;
+
+    if height_of(movement.to).is_none() {
+        return Err(MoveError::MissingDestination {
+            line,
+            column: movement.to,
+        });
     }
 
-    for crate_ in moving_crates {
-        positions.get_mut(&(movement_info.to)).unwrap().push(crate_);
+    if height < movement.crates_count {
+        return Err(MoveError::NotEnoughCrates {
+            line,
+            column: movement.from,
+            crates_count: movement.crates_count,
+            height,
+        });
     }
+
+    Ok(())
 }
 
-fn move_crates_v2(positions: &mut HashMap<usize, Vec<char>>, row: &str) {
-    let movement_info = get_movement_info(row);
+/// Checks that all moves can be applied in order, `first_line` being the line number of the
+/// first move.
+///
+/// Only stack heights matter, so this is the same for every crane.
+///
+/// # Errors
+/// Returns an error on the first move that cannot be applied
+pub fn validate_moves(
+    positions: &HashMap<usize, Vec<String>>,
+    moves: &[MovementInfo],
+    first_line: usize,
+) -> Result<(), MoveError> {
+    let mut heights: HashMap<usize, usize> = positions.iter().map(|(k, v)| (*k, v.len())).collect();
 
-    let mut moving_crates = vec![];
+    for (idx, movement) in moves.iter().enumerate() {
+        check_heights(
+            |column| heights.get(&column).copied(),
+            movement,
+            first_line + idx,
+        )?;
 
-    for _ in 0..movement_info.crates_count {
-        moving_crates.push(
-            positions
-                .get_mut(&(movement_info.from))
-                .unwrap()
-                .pop()
-                .unwrap(),
-        );
+        *heights.get_mut(&movement.from).unwrap() -= movement.crates_count;
+        *heights.get_mut(&movement.to).unwrap() += movement.crates_count;
+    }
+
+    Ok(())
+}
+
+/// Applies a movement and returns the moved crates, from bottom to top as they were on the
+/// origin stack
+fn apply_movement(
+    positions: &mut HashMap<usize, Vec<String>>,
+    movement_info: &MovementInfo,
+    crane: &dyn Crane,
+) -> Vec<String> {
+    let from = positions
+        .get_mut(&(movement_info.from))
+        .expect("Moves are validated before being applied");
+    let moving_crates = from.split_off(from.len() - movement_info.crates_count);
+
+    let to = positions
+        .get_mut(&(movement_info.to))
+        .expect("Moves are validated before being applied");
+
+    to.extend(
+        crane
+            .arrange(moving_crates.len())
+            .into_iter()
+            .map(|idx| moving_crates[idx].clone()),
+    );
+
+    moving_crates
+}
+
+/// Reverts a movement, taking the crates back from the destination to the origin stack
+fn unapply_movement(
+    positions: &mut HashMap<usize, Vec<String>>,
+    movement_info: &MovementInfo,
+    crane: &dyn Crane,
+) {
+    let to = positions
+        .get_mut(&(movement_info.to))
+        .expect("Moves are validated before being reverted");
+    let landed_crates = to.split_off(to.len() - movement_info.crates_count);
+
+    // landed_crates[i] was picked_crates[arrangement[i]]
+    let mut picked_crates = vec![String::new(); landed_crates.len()];
+    for (landed, picked_idx) in landed_crates
+        .into_iter()
+        .zip(crane.arrange(movement_info.crates_count))
+    {
+        picked_crates[picked_idx] = landed;
     }
 
-    for crate_ in moving_crates.iter().rev() {
-        positions
-            .get_mut(&(movement_info.to))
-            .unwrap()
-            .push(*crate_);
+    positions
+        .get_mut(&(movement_info.from))
+        .expect("Moves are validated before being reverted")
+        .extend(picked_crates);
+}
+
+/// Reconstructs the stacks as they were before `moves`, from the stacks after them.
+///
+/// Moves are reverted from last to first, so this is the inverse of applying them with `crane`.
+/// Errors name moves by their number, starting at 1.
+///
+/// # Errors
+/// Returns an error on the first reverted move that takes more crates than its destination holds
+/// or that uses a missing column
+///
+/// # Examples
+///
+/// ```
+/// use std::collections::HashMap;
+/// use advent_of_code_2022::day_5::{reverse_moves, CrateMoverVersion, MovementInfo};
+///
+/// let final_positions = HashMap::from([
+///     (1, vec![]),
+///     (2, vec!["A".to_string(), "B".to_string()]),
+/// ]);
+/// let moves = [MovementInfo { crates_count: 2, from: 1, to: 2 }];
+///
+/// let start = reverse_moves(&final_positions, &moves, &CrateMoverVersion::V1).unwrap();
+/// assert_eq!(start[&1], vec!["B".to_string(), "A".to_string()]);
+/// assert!(start[&2].is_empty());
+/// ```
+pub fn reverse_moves(
+    final_positions: &HashMap<usize, Vec<String>>,
+    moves: &[MovementInfo],
+    crane: &dyn Crane,
+) -> Result<HashMap<usize, Vec<String>>, MoveError> {
+    let mut positions = final_positions.clone();
+
+    revert_moves(&mut positions, moves, 1, crane)?;
+
+    Ok(positions)
+}
+
+/// Returns the stacks as they were after the first `move_count` moves, from the stacks after all
+/// of them, without replaying from the start.
+///
+/// # Errors
+/// Returns an error if one of the moves after `move_count` cannot be reverted
+///
+/// # Panics
+/// Panics if there are fewer than `move_count` moves
+pub fn positions_after_move(
+    final_positions: &HashMap<usize, Vec<String>>,
+    moves: &[MovementInfo],
+    move_count: usize,
+    crane: &dyn Crane,
+) -> Result<HashMap<usize, Vec<String>>, MoveError> {
+    assert!(
+        move_count <= moves.len(),
+        "Cannot go back to move {move_count}, there are only {} moves",
+        moves.len()
+    );
+
+    let mut positions = final_positions.clone();
+
+    revert_moves(&mut positions, &moves[move_count..], move_count + 1, crane)?;
+
+    Ok(positions)
+}
+
+/// Reverts `moves` from last to first, `first_line` being the number of the first move
+fn revert_moves(
+    positions: &mut HashMap<usize, Vec<String>>,
+    moves: &[MovementInfo],
+    first_line: usize,
+    crane: &dyn Crane,
+) -> Result<(), MoveError> {
+    for (idx, movement) in moves.iter().enumerate().rev() {
+        // Reverting a move is moving crates back, so we check it as such
+        let reverse_movement = MovementInfo {
+            crates_count: movement.crates_count,
+            from: movement.to,
+            to: movement.from,
+        };
+        check_movement(positions, &reverse_movement, first_line + idx)?;
+
+        unapply_movement(positions, movement, crane);
     }
+
+    Ok(())
 }
 
-fn get_top_crates(positions: &HashMap<usize, Vec<char>>) -> String {
+fn get_top_crates(positions: &HashMap<usize, Vec<String>>) -> String {
     positions
         .iter()
         .sorted_by(|a, b| Ord::cmp(&a.0, &b.0))
         .fold(String::new(), |result, (_, column)| {
-            result
-                + column
-                    .last()
-                    .expect("No crate on column {column}")
-                    .to_string()
-                    .as_str()
+            // Empty columns get a space so the other crates keep their position
+            result + column.last().map_or(" ", String::as_str)
         })
 }
 
@@ -185,6 +956,19 @@ fn get_top_crates(positions: &HashMap<usize, Vec<char>>) -> String {
 mod tests {
     use super::*;
 
+    /// Builds a stack of single letter crates from bottom to top
+    fn crates(labels: &str) -> Vec<String> {
+        labels.chars().map(String::from).collect()
+    }
+
+    fn get_movement_info(row: &str) -> MovementInfo {
+        movement_info(row).unwrap().1
+    }
+
+    fn move_crates(positions: &mut HashMap<usize, Vec<String>>, row: &str) {
+        apply_movement(positions, &get_movement_info(row), &CrateMoverVersion::V1);
+    }
+
     const DEMO_INPUT: &str = "    [D]    
 [N] [C]    
 [Z] [M] [P]
@@ -211,7 +995,7 @@ move 1 from 1 to 2";
                 "[D]
  1"
             ),
-            HashMap::from([(1, vec!['D'])])
+            HashMap::from([(1, crates("D"))])
         );
 
         assert_eq!(
@@ -221,94 +1005,432 @@ move 1 from 1 to 2";
 [Z] [M] [P]
  1   2   3 "
             ),
-            HashMap::from([
-                (1, vec!['Z', 'N']),
-                (2, vec!['M', 'C', 'D']),
-                (3, vec!['P'])
-            ])
+            HashMap::from([(1, crates("ZN")), (2, crates("MCD")), (3, crates("P"))])
+        );
+    }
+
+    #[test]
+    fn test_draw_positions() {
+        let (drawing, _) = DEMO_INPUT.split_once("\n\n").unwrap();
+        assert_eq!(draw_positions(&read_starting_position(drawing)), drawing);
+
+        // Empty columns survive the round-trip
+        let positions = HashMap::from([(1, crates("")), (2, crates("A")), (3, crates(""))]);
+        let drawing = draw_positions(&positions);
+        assert_eq!(drawing, "    [A]    \n 1   2   3 ");
+        assert_eq!(read_starting_position(&drawing), positions);
+
+        assert_eq!(draw_positions(&HashMap::new()), "");
+    }
+
+    #[test]
+    fn test_display_yard() {
+        let mut yard = CrateYard::from_input(DEMO_INPUT, CrateMoverVersion::V1).unwrap();
+        yard.run();
+
+        assert_eq!(
+            yard.to_string(),
+            "        [Z]
+        [N]
+        [D]
+[C] [M] [P]
+ 1   2   3 "
         );
     }
 
     #[test]
     fn test_move() {
-        let mut positions = HashMap::from([
-            (1, vec!['Z', 'N']),
-            (2, vec!['M', 'C', 'D']),
-            (3, vec!['P']),
-        ]);
+        let mut positions =
+            HashMap::from([(1, crates("ZN")), (2, crates("MCD")), (3, crates("P"))]);
 
         move_crates(&mut positions, "move 1 from 2 to 1");
 
         assert_eq!(
             positions,
-            HashMap::from([
-                (1, vec!['Z', 'N', 'D']),
-                (2, vec!['M', 'C']),
-                (3, vec!['P']),
-            ])
+            HashMap::from([(1, crates("ZND")), (2, crates("MC")), (3, crates("P")),])
         );
 
         move_crates(&mut positions, "move 2 from 2 to 3");
 
         assert_eq!(
             positions,
-            HashMap::from([
-                (1, vec!['Z', 'N', 'D']),
-                (2, vec![]),
-                (3, vec!['P', 'C', 'M']),
-            ])
+            HashMap::from([(1, crates("ZND")), (2, crates("")), (3, crates("PCM")),])
         );
 
         move_crates(&mut positions, "move 2 from 1 to 3");
 
         assert_eq!(
             positions,
-            HashMap::from([
-                (1, vec!['Z']),
-                (2, vec![]),
-                (3, vec!['P', 'C', 'M', 'D', 'N']),
+            HashMap::from([(1, crates("Z")), (2, crates("")), (3, crates("PCMDN")),])
+        );
+    }
+
+    #[test]
+    fn test_move_v2() {
+        let mut positions = HashMap::from([(1, crates("ZN")), (2, crates("MCD"))]);
+
+        let moved = apply_movement(
+            &mut positions,
+            &get_movement_info("move 2 from 2 to 1"),
+            &CrateMoverVersion::V2,
+        );
+
+        assert_eq!(moved, crates("CD"));
+        assert_eq!(
+            positions,
+            HashMap::from([(1, crates("ZNCD")), (2, crates("M"))])
+        );
+    }
+
+    #[test]
+    fn test_crate_yard() {
+        let mut yard = CrateYard::from_input(DEMO_INPUT, CrateMoverVersion::V1).unwrap();
+        let start = yard.positions().clone();
+
+        assert_eq!(yard.moves().len(), 4);
+        assert_eq!(yard.top_crates(), "NDP");
+
+        let step = yard.step().unwrap();
+        assert_eq!(step.crates, crates("D"));
+        assert_eq!(yard.top_crates(), "DCP");
+
+        let step = yard.step().unwrap();
+        assert_eq!(step.crates, crates("ZND"));
+        assert_eq!(yard.positions()[&3], crates("PDNZ"));
+
+        let step = yard.undo().unwrap();
+        assert_eq!(step.movement, get_movement_info("move 3 from 1 to 3"));
+        assert_eq!(yard.top_crates(), "DCP");
+        assert_eq!(yard.current_move(), 1);
+
+        yard.redo();
+        yard.run();
+        assert_eq!(yard.top_crates(), "CMZ");
+        assert!(yard.step().is_none());
+        assert_eq!(
+            yard.history()
+                .iter()
+                .map(|s| s.crates.len())
+                .collect::<Vec<_>>(),
+            vec![1, 3, 2, 1]
+        );
+
+        yard.jump_to(0);
+        assert_eq!(yard.positions(), &start);
+        assert!(yard.undo().is_none());
+
+        yard.jump_to(2);
+        yard.push_move(get_movement_info("move 1 from 3 to 1"))
+            .unwrap();
+        assert_eq!(yard.moves().len(), 3);
+        assert_eq!(yard.top_crates(), "ZCN");
+    }
+
+    #[test]
+    fn test_crate_yard_v2() {
+        let mut yard = CrateYard::from_input(DEMO_INPUT, CrateMoverVersion::V2).unwrap();
+
+        yard.jump_to(2);
+        assert_eq!(yard.positions()[&3], crates("PZND"));
+
+        yard.jump_to(1);
+        assert_eq!(yard.positions()[&1], crates("ZND"));
+        assert_eq!(yard.positions()[&3], crates("P"));
+    }
+
+    #[test]
+    fn test_cranes() {
+        assert_eq!(CrateMoverVersion::V1.arrange(3), vec![2, 1, 0]);
+        assert_eq!(CrateMoverVersion::V2.arrange(3), vec![0, 1, 2]);
+        assert_eq!(MaxLiftCrane::new(2).arrange(5), vec![3, 4, 1, 2, 0]);
+        assert_eq!(MaxLiftCrane::new(1).arrange(3), vec![2, 1, 0]);
+        assert_eq!(MaxLiftCrane::new(9).arrange(3), vec![0, 1, 2]);
+        assert_eq!(RotatingCrane::new(4).arrange(3), vec![1, 2, 0]);
+        assert_eq!(RotatingCrane::new(4).arrange(0), vec![]);
+
+        // A single crane behaves like the first version and a big one like the second
+        assert_eq!(find_top_crates(DEMO_INPUT, &MaxLiftCrane::new(1)), "CMZ");
+        assert_eq!(find_top_crates(DEMO_INPUT, &MaxLiftCrane::new(3)), "MCD");
+        assert_eq!(find_top_crates(DEMO_INPUT, &MaxLiftCrane::new(2)), "MCZ");
+        assert_eq!(find_top_crates(DEMO_INPUT, &RotatingCrane::new(1)), "CMZ");
+    }
+
+    #[test]
+    fn test_crane_names() {
+        for name in [
+            "v1",
+            "v2",
+            "max-lift-3",
+            "rotating-2",
+            "metered-5-metered-1-v1",
+        ] {
+            assert_eq!(crane_from_name(name).unwrap().name(), name);
+        }
+
+        for name in [
+            "v3",
+            "max-lift-",
+            "max-lift-0",
+            "rotating-a",
+            "metered-5",
+            "metered-x-v1",
+        ] {
+            assert!(crane_from_name(name).is_none());
+        }
+    }
+
+    #[test]
+    fn test_crane_cost() {
+        let crane = MeteredCrane::new(CrateMoverVersion::V2, 10);
+
+        assert_eq!(
+            find_top_crates_and_cost(DEMO_INPUT, &crane),
+            ("MCD".to_string(), 70)
+        );
+
+        let mut yard = CrateYard::from_input(DEMO_INPUT, crane).unwrap();
+        yard.jump_to(2);
+        assert_eq!(yard.total_cost(), 40);
+        assert_eq!(yard.history()[1].cost, 30);
+        yard.undo();
+        assert_eq!(yard.total_cost(), 10);
+        assert_eq!(yard.crane().name(), "metered-10-v2");
+    }
+
+    #[test]
+    fn test_reverse_moves() {
+        let (drawing, moves) = DEMO_INPUT.split_once("\n\n").unwrap();
+        let start = read_starting_position(drawing);
+        let moves = parse_movements(moves, 1).unwrap();
+
+        for crane in [
+            Box::new(CrateMoverVersion::V1) as Box<dyn Crane>,
+            Box::new(CrateMoverVersion::V2),
+            Box::new(MaxLiftCrane::new(2)),
+            Box::new(RotatingCrane::new(1)),
+        ] {
+            let mut yard = CrateYard::new(start.clone(), moves.clone(), &crane).unwrap();
+            yard.run();
+            let final_positions = yard.positions().clone();
+
+            assert_eq!(
+                reverse_moves(&final_positions, &moves, &crane),
+                Ok(start.clone())
+            );
+
+            for move_count in 0..=moves.len() {
+                yard.jump_to(move_count);
+
+                assert_eq!(
+                    positions_after_move(&final_positions, &moves, move_count, &crane).as_ref(),
+                    Ok(yard.positions())
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_reverse_moves_errors() {
+        let final_positions = HashMap::from([(1, crates("AB")), (2, crates("C"))]);
+        let moves = parse_movements("move 1 from 1 to 2\nmove 2 from 1 to 2", 1).unwrap();
+
+        assert_eq!(
+            reverse_moves(&final_positions, &moves, &CrateMoverVersion::V1),
+            Err(MoveError::NotEnoughCrates {
+                line: 2,
+                column: 2,
+                crates_count: 2,
+                height: 1
+            })
+        );
+
+        let moves = parse_movements("move 1 from 1 to 3", 1).unwrap();
+        assert_eq!(
+            positions_after_move(&final_positions, &moves, 0, &CrateMoverVersion::V2),
+            Err(MoveError::MissingOrigin { line: 1, column: 3 })
+        );
+    }
+
+    #[test]
+    fn test_parse_movements() {
+        assert_eq!(
+            parse_movements("move 12 from 2 to 10\nmove 1 from 1 to 2 ", 1),
+            Ok(vec![
+                MovementInfo {
+                    crates_count: 12,
+                    from: 2,
+                    to: 10
+                },
+                MovementInfo {
+                    crates_count: 1,
+                    from: 1,
+                    to: 2
+                }
             ])
         );
+        assert_eq!(
+            parse_movements("move 1 from 2 to 1\nmove 1 from two to 1", 6),
+            Err(MoveError::Misformed {
+                line: 7,
+                row: "move 1 from two to 1".to_string()
+            })
+        );
+        assert!(parse_movements("move 1 from 2 to 1 please", 1).is_err());
+    }
+
+    #[test]
+    fn test_move_errors() {
+        let input = |moves: &str| {
+            CrateYard::from_input(
+                &format!("[A]    \n[B] [C]\n 1   2 \n\n{moves}"),
+                CrateMoverVersion::V1,
+            )
+            .map(|yard| yard.top_crates())
+        };
+
+        assert_eq!(input("move 2 from 1 to 2"), Ok("AC".to_string()));
+        assert_eq!(
+            input("move 1 from 3 to 2"),
+            Err(MoveError::MissingOrigin { line: 5, column: 3 })
+        );
+        assert_eq!(
+            input("move 1 from 2 to 1\nmove 1 from 1 to 0"),
+            Err(MoveError::MissingDestination { line: 6, column: 0 })
+        );
+        assert_eq!(
+            input("move 2 from 1 to 2\nmove 4 from 2 to 1"),
+            Err(MoveError::NotEnoughCrates {
+                line: 6,
+                column: 2,
+                crates_count: 4,
+                height: 3
+            })
+        );
+        assert_eq!(
+            input("move 2 from 1 to 2\nmove 4 from 2 to 1")
+                .unwrap_err()
+                .to_string(),
+            "Line 6: cannot take 4 crates from column 2, which holds 3"
+        );
+
+        let mut yard = CrateYard::new(
+            HashMap::from([(1, crates("A")), (2, crates(""))]),
+            vec![],
+            CrateMoverVersion::V1,
+        )
+        .unwrap();
+        assert_eq!(
+            yard.push_move(get_movement_info("move 1 from 2 to 1")),
+            Err(MoveError::NotEnoughCrates {
+                line: 1,
+                column: 2,
+                crates_count: 1,
+                height: 0
+            })
+        );
+        assert!(yard.moves().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Line 5: cannot take crates from missing column 3")]
+    fn test_find_top_crates_panic() {
+        let _ = find_top_crates(
+            "[A]\n 1 \n\nmove 1 from 1 to 1\nmove 1 from 3 to 1",
+            &CrateMoverVersion::V1,
+        );
     }
 
     #[test]
     fn test_get_top_crates() {
-        let positions = HashMap::from([
-            (1, vec!['D', 'D', 'D']),
-            (2, vec!['C', 'C']),
-            (3, vec!['P']),
-        ]);
+        let positions = HashMap::from([(1, crates("DDD")), (2, crates("CC")), (3, crates("P"))]);
 
         assert_eq!(get_top_crates(&positions), "DCP");
 
-        let positions = HashMap::from([
-            (1, vec!['Z']),
-            (2, vec!['C', 'C', 'C', 'C']),
-            (3, vec!['P']),
-        ]);
+        let positions = HashMap::from([(1, crates("Z")), (2, crates("CCCC")), (3, crates("P"))]);
 
         assert_eq!(get_top_crates(&positions), "ZCP");
+
+        let positions = HashMap::from([(1, crates("A")), (2, vec![]), (3, crates("B"))]);
+
+        assert_eq!(get_top_crates(&positions), "A B");
+    }
+
+    #[test]
+    fn test_top_crates_empty_column() {
+        let positions = HashMap::from([(1, crates("A")), (2, vec![])]);
+        let yard = CrateYard::new(positions, vec![], CrateMoverVersion::V1).unwrap();
+
+        assert_eq!(yard.top_crates(), "A ");
     }
 
     #[test]
     fn test_crate_rows() {
         assert_eq!(
-            crate_rows("[A] [B] [C]"),
-            Ok(("", vec![vec![Some('A'), Some('B'), Some('C')]]))
+            crate_row("[A] [B] [C]"),
+            Ok(("", vec![(0..3, "A"), (4..7, "B"), (8..11, "C")]))
         );
         assert_eq!(
-            crate_rows(
-                "[D]     [F]
-[A] [B] [C]"
-            ),
-            Ok((
-                "",
-                vec![
-                    vec![Some('D'), None, Some('F')],
-                    vec![Some('A'), Some('B'), Some('C')]
-                ]
-            ))
+            crate_row("[D]     [F] 1"),
+            Ok((" 1", vec![(0..3, "D"), (8..11, "F")]))
         );
+        assert_eq!(
+            crate_row("  [AB]  [c-1]"),
+            Ok(("", vec![(2..6, "AB"), (8..13, "c-1")]))
+        );
+        assert_eq!(
+            column_numbers(" 1   2  10 "),
+            Ok((" ", vec![(1..2, 1), (5..6, 2), (8..10, 10)]))
+        );
+    }
+
+    #[test]
+    fn test_wide_drawing() {
+        let drawing = "[AB]           [X]
+[C]  [DEF]     [Y]
+ 1    2    3    4";
+
+        let positions = read_starting_position(drawing);
+        assert_eq!(positions[&1], vec!["C".to_string(), "AB".to_string()]);
+        assert_eq!(positions[&2], vec!["DEF".to_string()]);
+        assert_eq!(positions[&3], Vec::<String>::new());
+        assert_eq!(positions[&4], crates("YX"));
+
+        assert_eq!(
+            draw_positions(&positions),
+            "[AB]               [X] 
+ [C]  [DEF]        [Y] 
+  1     2     3     4  "
+        );
+        assert_eq!(
+            read_starting_position(&draw_positions(&positions)),
+            positions
+        );
+    }
+
+    #[test]
+    fn test_many_stacks() {
+        let mut positions: HashMap<usize, Vec<String>> =
+            (1..=12).map(|column| (column, crates("A"))).collect();
+        positions.get_mut(&11).unwrap().push("Z".to_string());
+
+        let drawing = draw_positions(&positions);
+        assert_eq!(
+            drawing.lines().last(),
+            Some(" 1   2   3   4   5   6   7   8   9  10  11  12 ")
+        );
+        assert_eq!(read_starting_position(&drawing), positions);
+
+        let input = format!("{drawing}\n\nmove 1 from 11 to 12");
+        assert_eq!(
+            find_top_crates(&input, &CrateMoverVersion::V2),
+            "AAAAAAAAAAAZ"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot parse base line")]
+    fn test_missing_base_line_panic() {
+        let _ = read_starting_position("[A] [B]");
     }
 
     #[test]
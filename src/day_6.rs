@@ -1,14 +1,288 @@
-use itertools::Itertools;
 use std::collections::VecDeque;
+use std::io::{self, BufReader, Read};
+
+/// Sliding window detector for markers: sequences of `distinct_letters` distinct bytes.
+///
+/// It keeps the count of each byte in the window, so every new byte is handled in constant time.
+/// Bytes can be pushed one at a time, which makes it usable on live data.
+///
+/// # Examples
+///
+/// ```
+/// use advent_of_code_2022::day_6::MarkerDetector;
+///
+/// let mut detector = MarkerDetector::new(3);
+///
+/// assert_eq!(detector.push(b'A'), None);
+/// assert_eq!(detector.push(b'A'), None);
+/// assert_eq!(detector.push(b'B'), None);
+/// assert_eq!(detector.push(b'C'), Some(4));
+/// assert_eq!(detector.push(b'A'), Some(5));
+/// ```
+#[derive(Debug, Clone)]
+pub struct MarkerDetector {
+    distinct_letters: usize,
+    window: VecDeque<u8>,
+    counts: [usize; 256],
+    // Number of bytes with a count of exactly one in the window
+    unique_count: usize,
+    position: usize,
+}
+
+impl MarkerDetector {
+    /// # Panics
+    /// Panics if `distinct_letters` is 0
+    #[must_use]
+    pub fn new(distinct_letters: usize) -> Self {
+        assert!(distinct_letters > 0, "Markers need at least one letter");
+
+        Self {
+            distinct_letters,
+            window: VecDeque::with_capacity(distinct_letters + 1),
+            counts: [0; 256],
+            unique_count: 0,
+            position: 0,
+        }
+    }
+
+    /// Adds a byte to the window.
+    ///
+    /// Returns the number of bytes read so far (starting at 1) if the window is now a marker.
+    pub fn push(&mut self, byte: u8) -> Option<usize> {
+        self.position += 1;
+
+        self.window.push_back(byte);
+        self.add_count(byte, true);
+
+        if self.window.len() > self.distinct_letters {
+            let old_byte = self.window.pop_front().expect("The window is not empty");
+            self.add_count(old_byte, false);
+        }
+
+        (self.unique_count == self.distinct_letters).then_some(self.position)
+    }
+
+    /// Returns the number of bytes read so far
+    #[must_use]
+    pub const fn position(&self) -> usize {
+        self.position
+    }
+
+    fn add_count(&mut self, byte: u8, increment: bool) {
+        let count = &mut self.counts[byte as usize];
+
+        // A byte stops or starts being unique when its count leaves or reaches one
+        if *count == 1 {
+            self.unique_count -= 1;
+        }
+
+        if increment {
+            *count += 1;
+        } else {
+            *count -= 1;
+        }
+
+        if *count == 1 {
+            self.unique_count += 1;
+        }
+    }
+}
+
+/// Returns the byte offset right after every marker in `input`, in order.
+///
+/// Markers are made of distinct bytes, so a multi-byte character counts as several symbols.
+pub fn marker_positions(input: &str, distinct_letters: usize) -> impl Iterator<Item = usize> + '_ {
+    let mut detector = MarkerDetector::new(distinct_letters);
+
+    input.bytes().filter_map(move |byte| detector.push(byte))
+}
+
+/// Returns the byte offset right after the first marker, if there is one.
+///
+/// Like [`marker_positions`], this works on bytes rather than characters.
+#[must_use]
+pub fn find_marker(input: &str, distinct_letters: usize) -> Option<usize> {
+    marker_positions(input, distinct_letters).next()
+}
+
+/// Same as [`marker_positions`] on a byte stream, reading it as markers are needed
+pub fn stream_marker_positions<R: Read>(
+    reader: R,
+    distinct_letters: usize,
+) -> impl Iterator<Item = io::Result<usize>> {
+    let mut detector = MarkerDetector::new(distinct_letters);
+
+    BufReader::new(reader)
+        .bytes()
+        .filter_map(move |byte| match byte {
+            Ok(byte) => detector.push(byte).map(Ok),
+            Err(err) => Some(Err(err)),
+        })
+}
+
+/// A message of a [`Frame`], introduced by a start-of-message marker.
+///
+/// Offsets are byte offsets in the whole datastream, starting at 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Message<'a> {
+    /// Offset of the first byte of the start-of-message marker
+    pub marker_start: usize,
+    /// Offset of the first byte of the payload, right after the marker
+    pub start: usize,
+    /// Offset right after the last byte of the payload
+    pub end: usize,
+    pub payload: &'a [u8],
+}
+
+/// A frame of a datastream, opened by a start-of-packet marker
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame<'a> {
+    /// Offset of the first byte of the start-of-packet marker
+    pub marker_start: usize,
+    /// Offset right after the start-of-packet marker
+    pub start: usize,
+    /// Offset right after the last byte of the frame
+    pub end: usize,
+    /// Bytes between the start-of-packet marker and the first message
+    pub header: &'a [u8],
+    pub messages: Vec<Message<'a>>,
+}
+
+/// Marker lengths used to decode a datastream into [`Frame`]s
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Protocol {
+    pub packet_marker: usize,
+    pub message_marker: usize,
+}
+
+impl Default for Protocol {
+    /// The communication device protocol: 4 distinct bytes for packets and 14 for messages
+    fn default() -> Self {
+        Self {
+            packet_marker: 4,
+            message_marker: 14,
+        }
+    }
+}
+
+impl Protocol {
+    /// Splits a datastream into frames.
+    ///
+    /// Each start-of-packet marker opens a frame and whatever comes before the first one is noise.
+    /// In the frame, each start-of-message marker opens a message whose payload runs until the
+    /// next start-of-message marker. Once the frame has no more messages, the next start-of-packet
+    /// marker closes it and opens the next frame, or the frame runs until the end of the
+    /// datastream. Markers never overlap: the search for the next one starts after the previous
+    /// one.
+    ///
+    /// A trailing line ending is not part of the datastream. Markers are made of bytes, so offsets
+    /// are byte offsets, and headers and payloads are raw bytes that may split a multi-byte
+    /// character.
+    ///
+    /// # Panics
+    /// Panics if one of the marker lengths is 0
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use advent_of_code_2022::day_6::Protocol;
+    ///
+    /// let protocol = Protocol { packet_marker: 2, message_marker: 3 };
+    /// let frames = protocol.decode("aabxxabcyyzbcd\n");
+    ///
+    /// assert_eq!(frames.len(), 2);
+    /// assert_eq!(frames[0].start, 3);
+    /// assert_eq!(frames[0].header, b"x");
+    /// assert_eq!(frames[0].messages[0].payload, b"cy");
+    /// // The "cd" start-of-packet marker closes the first frame and opens the second one
+    /// assert_eq!(frames[0].end, 12);
+    /// assert_eq!(frames[1].start, 14);
+    /// ```
+    #[must_use]
+    pub fn decode<'a>(&self, datastream: &'a str) -> Vec<Frame<'a>> {
+        assert!(
+            self.packet_marker > 0 && self.message_marker > 0,
+            "Markers need at least one letter"
+        );
+
+        let bytes = datastream.trim_end_matches(['\n', '\r']).as_bytes();
+        let mut frames = vec![];
+        // Once a message search reaches the end of the datastream, no later one can succeed
+        let mut no_messages_from = usize::MAX;
+        let mut next_packet = next_marker(bytes, 0, self.packet_marker);
+
+        while let Some(start) = next_packet {
+            let mut messages: Vec<Message> = vec![];
+            let mut cursor = start;
+
+            while cursor < no_messages_from {
+                let Some(message_start) = next_marker(bytes, cursor, self.message_marker) else {
+                    no_messages_from = cursor;
+                    break;
+                };
+                let marker_start = message_start - self.message_marker;
+
+                if let Some(last_message) = messages.last_mut() {
+                    last_message.end = marker_start;
+                }
+
+                messages.push(Message {
+                    marker_start,
+                    start: message_start,
+                    end: bytes.len(),
+                    payload: &[],
+                });
+                cursor = message_start;
+            }
+
+            next_packet = next_marker(bytes, cursor, self.packet_marker);
+            let end = next_packet.map_or(bytes.len(), |next| next - self.packet_marker);
+
+            if let Some(last_message) = messages.last_mut() {
+                last_message.end = end;
+            }
+            for message in &mut messages {
+                message.payload = &bytes[message.start..message.end];
+            }
+
+            let header_end = messages.first().map_or(end, |m| m.marker_start);
+
+            frames.push(Frame {
+                marker_start: start - self.packet_marker,
+                start,
+                end,
+                header: &bytes[start..header_end],
+                messages,
+            });
+        }
+
+        frames
+    }
+}
+
+// Returns the offset right after the first marker starting at or after `from`
+fn next_marker(bytes: &[u8], from: usize, distinct_letters: usize) -> Option<usize> {
+    let mut detector = MarkerDetector::new(distinct_letters);
+
+    bytes[from..]
+        .iter()
+        .find_map(|&byte| detector.push(byte))
+        .map(|position| from + position)
+}
 
 /// Returns the starting index (starting at 1) of the first packet in the given input string.
 ///
-/// A packet is defined as a sequence of `distinct_letters` distinct letters in the input string.
+/// A packet starts right after a marker: a sequence of `distinct_letters` distinct bytes. The index
+/// is a byte offset, and a multi-byte character counts as several bytes.
 ///
 /// # Arguments
 ///
 /// * `input` - A string slice containing the input text to search for packets in.
-/// * `distinct_letters` - The number of distinct letters that a packet should contain.
+/// * `distinct_letters` - The number of distinct bytes that a marker should contain.
+///
+/// # Panics
+/// Panics if there is no marker, see [`find_marker`] for a version that doesn't
+///
 /// # Examples
 ///
 /// ```
@@ -20,34 +294,14 @@ use std::collections::VecDeque;
 /// ```
 #[must_use]
 pub fn get_packet_start(input: &str, distinct_letters: usize) -> usize {
-    let mut last_chars = VecDeque::new();
-
-    for (idx, current_char) in input.chars().enumerate() {
-        // We add the new character at the end
-        last_chars.push_back(current_char);
-
-        // If we don't have distinct_letters characters already, we continue
-        // This uses Rust itertools
-        if last_chars.len() < distinct_letters {
-            continue;
-        }
-
-        // We check if we have distinct_letters different characters
-        if last_chars.iter().unique().count() == distinct_letters {
-            // Index starts at one 😱
-            return idx + 1;
-        }
-
-        // Otherwise we pop the first value and continue
-        last_chars.pop_front();
-    }
-
-    panic!("No packet marker found in input: {input:?}");
+    find_marker(input, distinct_letters)
+        .unwrap_or_else(|| panic!("No packet marker found in input: {input:?}"))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use itertools::Itertools;
 
     #[test]
     fn test_start_packet_marker() {
@@ -74,4 +328,143 @@ mod tests {
     fn test_start_message_marker_panic() {
         let _ = get_packet_start("mmmmmmmmmmmmmmmmmmmmmmmmmmmmmmmmmmmmmmm", 15);
     }
+
+    #[test]
+    fn test_find_marker() {
+        assert_eq!(find_marker("mjqjpqmgbljsphdztnvjfqwrcgsmlb", 4), Some(7));
+        assert_eq!(find_marker("aaaa", 2), None);
+        assert_eq!(find_marker("", 1), None);
+        assert_eq!(find_marker("aaaa", 1), Some(1));
+        // 'é' is two distinct bytes, offsets count bytes
+        assert_eq!(find_marker("éa", 2), Some(2));
+        assert_eq!(find_marker("aéa", 3), Some(3));
+    }
+
+    #[test]
+    fn test_marker_positions() {
+        assert_eq!(
+            marker_positions("aabcbdd", 3).collect::<Vec<_>>(),
+            vec![4, 6]
+        );
+
+        // Every window is checked against a naive implementation
+        let input = "mjqjpqmgbljsphdztnvjfqwrcgsmlbnznrnfrfntjfmvfwmzdfjlvtqnbhcprsg";
+        for distinct_letters in 1..=14 {
+            let expected: Vec<usize> = (distinct_letters..=input.len())
+                .filter(|end| {
+                    input[end - distinct_letters..*end].chars().unique().count() == distinct_letters
+                })
+                .collect();
+
+            assert_eq!(
+                marker_positions(input, distinct_letters).collect::<Vec<_>>(),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_stream_marker_positions() {
+        let stream = "bvwbjplbgvbhsrlpgdmjqwftvncz".as_bytes();
+
+        let positions: Vec<usize> = stream_marker_positions(stream, 14)
+            .collect::<io::Result<_>>()
+            .unwrap();
+
+        assert_eq!(positions, vec![23, 24, 25, 26, 27, 28]);
+    }
+
+    #[test]
+    fn test_decode() {
+        let datastream = "mjqjpqmgbljsphdztnvjfqwrcgsmlb";
+
+        let frames = Protocol::default().decode(datastream);
+
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].marker_start, 3);
+        assert_eq!(frames[0].start, get_packet_start(datastream, 4));
+        assert_eq!(frames[0].header, b"gblj");
+        // The message marker search starts after the packet marker, so it ends later than the
+        // first start-of-message marker of the whole datastream. Then "gsml" is the next
+        // start-of-packet marker, which closes the frame right away.
+        assert_eq!(
+            frames[0].messages,
+            vec![Message {
+                marker_start: 11,
+                start: 25,
+                end: 25,
+                payload: b""
+            }]
+        );
+        assert_eq!(frames[0].end, 25);
+
+        assert_eq!(
+            (frames[1].marker_start, frames[1].start, frames[1].end),
+            (25, 29, datastream.len())
+        );
+        assert_eq!(frames[1].header, b"b");
+        assert!(frames[1].messages.is_empty());
+    }
+
+    #[test]
+    fn test_decode_frames() {
+        let protocol = Protocol {
+            packet_marker: 2,
+            message_marker: 3,
+        };
+
+        // Every "ab" opens a new frame on the same line
+        let frames = protocol.decode("aabaaabaaab\n");
+
+        assert_eq!(
+            frames
+                .iter()
+                .map(|f| (f.marker_start, f.start, f.end, f.header))
+                .collect::<Vec<_>>(),
+            vec![
+                (1, 3, 5, &b"aa"[..]),
+                (5, 7, 9, &b"aa"[..]),
+                (9, 11, 11, &b""[..])
+            ]
+        );
+
+        let frames = protocol.decode("xxabcdaxabccyyab");
+
+        assert_eq!(frames.len(), 1);
+        assert_eq!(
+            frames[0]
+                .messages
+                .iter()
+                .map(|m| (m.marker_start, m.start, m.end, m.payload))
+                .collect::<Vec<_>>(),
+            vec![
+                (3, 6, 7, &b"a"[..]),
+                (7, 10, 13, &b"ccy"[..]),
+                (13, 16, 16, &b""[..])
+            ]
+        );
+    }
+
+    #[test]
+    fn test_decode_non_ascii() {
+        let protocol = Protocol {
+            packet_marker: 2,
+            message_marker: 3,
+        };
+
+        // The packet marker ends in the middle of 'é', which must not panic
+        let frames = protocol.decode("aébcd");
+
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].start, 2);
+        assert_eq!(frames[0].header, b"");
+        assert_eq!(frames[0].messages[0].marker_start, 2);
+        assert_eq!(frames[0].messages[0].payload, b"d");
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one letter")]
+    fn test_detector_panic() {
+        let _ = MarkerDetector::new(0);
+    }
 }
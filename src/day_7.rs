@@ -1,106 +1,1065 @@
-use std::collections::HashMap;
+use std::cell::OnceCell;
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::{self, Display, Formatter, Write};
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::{env, fs};
 
-use nom::character::complete::{alpha1, space0};
-use nom::sequence::tuple;
-use nom::IResult;
+use nom::{
+    branch::alt,
+    bytes::complete::{is_not, tag},
+    character::complete::{alpha1, char, digit1, one_of, space0, space1},
+    combinator::{all_consuming, map, map_res, opt, rest, value, verify},
+    multi::{count, many0},
+    sequence::{pair, preceded, separated_pair, terminated, tuple},
+    IResult,
+};
 
-/// Gets a hashmap of path -> files size
-#[must_use] pub fn get_directories(input: &str) -> HashMap<String, usize> {
-    // cwd will point to the current directory as a vec of dir names
-    let mut cwd = Vec::new();
+/// Index of an entry in a [`FileSystem`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EntryId(usize);
 
-    // Directories will hold directory file sizes through their path
-    let mut directories = HashMap::new();
+#[derive(Debug, Clone)]
+pub enum EntryKind {
+    File { size: usize },
+    Directory { children: Vec<EntryId> },
+}
+
+/// A file or a directory of a [`FileSystem`]
+#[derive(Debug, Clone)]
+pub struct Entry {
+    name: String,
+    parent: Option<EntryId>,
+    kind: EntryKind,
+    // Total size of a directory, computed on demand and reset when its content changes. Kept out
+    // of the public kind so only the filesystem can fill it.
+    total_size: OnceCell<usize>,
+}
+
+impl Entry {
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the directory holding the entry, `None` for the root
+    #[must_use]
+    pub fn parent(&self) -> Option<EntryId> {
+        self.parent
+    }
+
+    #[must_use]
+    pub fn kind(&self) -> &EntryKind {
+        &self.kind
+    }
+
+    #[must_use]
+    pub fn is_dir(&self) -> bool {
+        matches!(self.kind, EntryKind::Directory { .. })
+    }
 
-    // First, we build our filesystem tree
-    // TODO Make it into its own function that returns directories
-    for command_input in input.split("$ ") {
-        // TODO This is once again disgusting but I want to get it done... I think nom can do that better
-        // -> The first split matches to "" since it starts with the pattern
-        if command_input.is_empty() {
-            continue;
+    /// Returns the entries of a directory, in the order they were added, or nothing for a file
+    #[must_use]
+    pub fn children(&self) -> &[EntryId] {
+        match &self.kind {
+            EntryKind::File { .. } => &[],
+            EntryKind::Directory { children, .. } => children,
         }
+    }
+}
 
-        let mut lines = command_input.lines();
-        let command = parse_command(lines.next().unwrap());
+/// An in-memory filesystem tree, rooted at `/`
+#[derive(Debug, Clone)]
+pub struct FileSystem {
+    entries: Vec<Entry>,
+}
 
-        // We apply the command
-        match command {
-            // Backards -> We remove one element from the cwd
-            Command::CD(CommandCD::Backwards) => {
-                cwd.pop();
+impl Default for FileSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FileSystem {
+    /// Creates a filesystem holding only the root directory
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            entries: vec![Entry {
+                name: "/".to_string(),
+                parent: None,
+                kind: EntryKind::Directory { children: vec![] },
+                total_size: OnceCell::new(),
+            }],
+        }
+    }
+
+    /// Builds the filesystem described by a shell transcript
+    ///
+    /// # Arguments
+    /// * `input` - A transcript of `cd`, `ls` and `pwd` commands and their output
+    ///
+    /// # Panics
+    /// Panics if the transcript is invalid, see [`FileSystem::parse_transcript`]
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use advent_of_code_2022::day_7::FileSystem;
+    ///
+    /// let fs = FileSystem::from_transcript("$ cd /\n$ ls\ndir a\n10 b.txt\n$ cd a\n$ ls\n5 c");
+    ///
+    /// assert_eq!(fs.size(fs.root()), 15);
+    /// assert_eq!(fs.lookup("/a/c").map(|c| fs.size(c)), Some(5));
+    /// ```
+    #[must_use]
+    pub fn from_transcript(input: &str) -> Self {
+        Self::parse_transcript(input).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Builds the filesystem described by a shell transcript
+    ///
+    /// Commands are lines starting with `$ `:
+    /// * `cd <path>`, with absolute or relative paths made of any number of names, `.` and `..`
+    /// * `ls`, with any flags. Its output can be `dir <name>`, `<size> <name>` or `ls -l` lines.
+    /// * `pwd`, whose output must match the current directory
+    ///
+    /// Directories we `cd` into are created if `ls` did not list them first.
+    ///
+    /// # Errors
+    /// Returns an error with the line number (starting at 1) of the first unknown command, invalid
+    /// output line, or move out of the filesystem.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use advent_of_code_2022::day_7::{FileSystem, TranscriptError};
+    ///
+    /// let fs = FileSystem::parse_transcript("$ cd /a/b-1/..\n$ pwd\n/a\n$ ls -l\n-rw-r--r-- 1 me me 12 Jan 1 00:00 c.txt")
+    ///     .unwrap();
+    /// assert_eq!(fs.lookup("/a/c.txt").map(|c| fs.size(c)), Some(12));
+    ///
+    /// assert_eq!(
+    ///     FileSystem::parse_transcript("$ cd /\n$ rm -rf a").unwrap_err(),
+    ///     TranscriptError::UnknownCommand { line: 2, command: "rm -rf a".to_string() }
+    /// );
+    /// ```
+    pub fn parse_transcript(input: &str) -> Result<Self, TranscriptError> {
+        let mut fs = Self::new();
+        let mut cwd = fs.root();
+        // The last command, whose output we are reading
+        let mut command = None;
+
+        for (idx, row) in input.lines().enumerate() {
+            let line = idx + 1;
+            let row = row.trim_end();
+
+            if row.is_empty() {
+                continue;
             }
 
-            // Forward -> We add one element to the cwd
-            Command::CD(CommandCD::Path(x)) => cwd.push(x),
+            if let Some(command_row) = row.strip_prefix('$') {
+                let command_row = command_row.trim_start();
 
-            // LS -> We parse the information
-            Command::LS => {
-                for line in lines {
-                    let (left_part, _) = line.split_once(' ').unwrap();
+                let (_, new_command) =
+                    nom_command(command_row).map_err(|_| TranscriptError::UnknownCommand {
+                        line,
+                        command: command_row.to_string(),
+                    })?;
 
-                    if let Ok(size) = left_part.parse::<usize>() {
-                            let dir_size = directories
-                                .entry(get_current_dir_name(&cwd))
-                                .or_insert(0_usize);
+                match &new_command {
+                    Command::CD(CommandCD::Backwards) => {
+                        cwd = fs.change_directory(cwd, "..", line)?
+                    }
+                    Command::CD(CommandCD::Path(path)) => {
+                        cwd = fs.change_directory(cwd, path, line)?
+                    }
+                    Command::LS | Command::Pwd => {}
+                }
 
-                            *dir_size += size;
+                command = Some(new_command);
+                continue;
+            }
+
+            match command {
+                Some(Command::LS) => match all_consuming(nom_listed_entry)(row) {
+                    Ok((_, ListedEntry::Directory(name))) => {
+                        fs.transcript_directory(cwd, name, line)?;
+                    }
+                    Ok((_, ListedEntry::File(name, size))) => {
+                        fs.transcript_file(cwd, name, size, line)?;
+                    }
+                    Ok((_, ListedEntry::Total | ListedEntry::Skipped)) => {}
+                    Err(_) => {
+                        return Err(TranscriptError::InvalidOutput {
+                            line,
+                            output: row.to_string(),
+                        })
                     }
+                },
+                Some(Command::Pwd) if row != fs.path(cwd) => {
+                    return Err(TranscriptError::WrongDirectory {
+                        line,
+                        expected: fs.path(cwd),
+                        found: row.to_string(),
+                    })
+                }
+                Some(Command::Pwd) => {}
+                _ => {
+                    return Err(TranscriptError::UnexpectedOutput {
+                        line,
+                        output: row.to_string(),
+                    })
                 }
             }
         }
+
+        Ok(fs)
+    }
+
+    // Follows a `cd` path from `cwd`, creating missing directories
+    fn change_directory(
+        &mut self,
+        cwd: EntryId,
+        path: &str,
+        line: usize,
+    ) -> Result<EntryId, TranscriptError> {
+        let start = if path.starts_with('/') {
+            self.root()
+        } else {
+            cwd
+        };
+
+        path.split('/').try_fold(start, |dir, name| match name {
+            "" | "." => Ok(dir),
+            ".." => self
+                .entry(dir)
+                .parent
+                .ok_or(TranscriptError::MoveAboveRoot { line }),
+            _ => self.transcript_directory(dir, name, line),
+        })
+    }
+
+    fn transcript_directory(
+        &mut self,
+        parent: EntryId,
+        name: &str,
+        line: usize,
+    ) -> Result<EntryId, TranscriptError> {
+        match self.child(parent, name) {
+            Some(existing) if !self.entry(existing).is_dir() => {
+                Err(TranscriptError::NotADirectory {
+                    line,
+                    path: self.path(existing),
+                })
+            }
+            _ => Ok(self.add_directory(parent, name)),
+        }
+    }
+
+    fn transcript_file(
+        &mut self,
+        parent: EntryId,
+        name: &str,
+        size: usize,
+        line: usize,
+    ) -> Result<EntryId, TranscriptError> {
+        match self.child(parent, name) {
+            Some(existing) if self.entry(existing).is_dir() => Err(TranscriptError::NotAFile {
+                line,
+                path: self.path(existing),
+            }),
+            _ => Ok(self.add_file(parent, name, size)),
+        }
+    }
+
+    #[must_use]
+    pub fn root(&self) -> EntryId {
+        EntryId(0)
+    }
+
+    /// # Panics
+    /// Panics if the id does not come from this filesystem
+    #[must_use]
+    pub fn entry(&self, id: EntryId) -> &Entry {
+        &self.entries[id.0]
+    }
+
+    /// Returns the entry called `name` in the directory `dir`
+    #[must_use]
+    pub fn child(&self, dir: EntryId, name: &str) -> Option<EntryId> {
+        self.entry(dir)
+            .children()
+            .iter()
+            .copied()
+            .find(|&child| self.entry(child).name == name)
+    }
+
+    /// Adds a directory called `name` in `parent` and returns it, or returns the existing one
+    ///
+    /// # Panics
+    /// Panics if `parent` is a file, if `name` is not a valid entry name or if it is already taken
+    /// by a file
+    pub fn add_directory(&mut self, parent: EntryId, name: &str) -> EntryId {
+        if let Some(existing) = self.child(parent, name) {
+            assert!(
+                self.entry(existing).is_dir(),
+                "{} is a file",
+                self.path(existing)
+            );
+            return existing;
+        }
+
+        self.add_entry(parent, name, EntryKind::Directory { children: vec![] })
+    }
+
+    /// Adds a file called `name` in `parent` and returns it. A file listed twice keeps its latest
+    /// size.
+    ///
+    /// # Panics
+    /// Panics if `parent` is a file, if `name` is not a valid entry name or if it is already taken
+    /// by a directory
+    pub fn add_file(&mut self, parent: EntryId, name: &str, size: usize) -> EntryId {
+        let id = match self.child(parent, name) {
+            Some(existing) => {
+                match &mut self.entries[existing.0].kind {
+                    EntryKind::File { size: old_size } => *old_size = size,
+                    EntryKind::Directory { .. } => panic!("{} is a directory", self.path(existing)),
+                }
+                existing
+            }
+            None => self.add_entry(parent, name, EntryKind::File { size }),
+        };
+
+        self.reset_sizes(parent);
+        id
     }
 
-    directories
+    // Names are single path components, so paths stay unambiguous and nothing can point outside
+    // the tree once materialised
+    fn add_entry(&mut self, parent: EntryId, name: &str, kind: EntryKind) -> EntryId {
+        assert!(
+            !name.is_empty() && name != "." && name != ".." && !name.contains('/'),
+            "Invalid entry name: {name:?}"
+        );
+
+        let id = EntryId(self.entries.len());
+
+        match &mut self.entries[parent.0].kind {
+            EntryKind::Directory { children, .. } => children.push(id),
+            EntryKind::File { .. } => panic!("{} is not a directory", self.path(parent)),
+        }
+
+        self.entries.push(Entry {
+            name: name.to_string(),
+            parent: Some(parent),
+            kind,
+            total_size: OnceCell::new(),
+        });
+
+        id
+    }
+
+    // Clears the cached sizes of a directory and all its ancestors
+    fn reset_sizes(&mut self, dir: EntryId) {
+        let mut current = Some(dir);
+
+        while let Some(id) = current {
+            self.entries[id.0].total_size.take();
+            current = self.entries[id.0].parent;
+        }
+    }
+
+    /// Returns the size of a file, or the total size of a directory's files and subdirectories.
+    ///
+    /// Directory sizes are cached. The first query computes all missing directory sizes at once, in
+    /// a single pass over the filesystem.
+    #[must_use]
+    pub fn size(&self, id: EntryId) -> usize {
+        match &self.entry(id).kind {
+            EntryKind::File { size } => *size,
+            EntryKind::Directory { .. } => {
+                self.entry(id).total_size.get().copied().unwrap_or_else(|| {
+                    let totals = self.totals();
+                    self.cache_sizes(&totals);
+                    totals[id.0]
+                })
+            }
+        }
+    }
+
+    // Computes the total size of every entry.
+    // Entries are always added after their parent, so going through them backwards handles every
+    // child before its parent, without recursion.
+    fn totals(&self) -> Vec<usize> {
+        let mut totals = vec![0; self.entries.len()];
+
+        for (idx, entry) in self.entries.iter().enumerate().rev() {
+            if let EntryKind::File { size } = entry.kind {
+                totals[idx] = size;
+            }
+
+            if let Some(parent) = entry.parent {
+                totals[parent.0] += totals[idx];
+            }
+        }
+
+        totals
+    }
+
+    fn cache_sizes(&self, totals: &[usize]) {
+        for (entry, &total) in self.entries.iter().zip(totals) {
+            if entry.is_dir() {
+                // Already cached sizes are still up to date
+                let _ = entry.total_size.set(total);
+            }
+        }
+    }
+
+    /// Returns the absolute path of an entry, like `/a/e`
+    #[must_use]
+    pub fn path(&self, id: EntryId) -> String {
+        let mut names = vec![];
+        let mut current = id;
+
+        while let Some(parent) = self.entry(current).parent {
+            names.push(self.entry(current).name.as_str());
+            current = parent;
+        }
+
+        names.reverse();
+        format!("/{}", names.join("/"))
+    }
+
+    /// Finds an entry from its absolute path, like `/a/e`
+    #[must_use]
+    pub fn lookup(&self, path: &str) -> Option<EntryId> {
+        path.split('/')
+            .filter(|name| !name.is_empty())
+            .try_fold(self.root(), |dir, name| self.child(dir, name))
+    }
+
+    /// Iterates over all entries in depth-first order, starting from the root
+    pub fn iter(&self) -> impl Iterator<Item = EntryId> + '_ {
+        let mut stack = vec![self.root()];
+
+        std::iter::from_fn(move || {
+            let id = stack.pop()?;
+            // Children are pushed in reverse so they come out in the order they were added
+            stack.extend(self.entry(id).children().iter().rev());
+            Some(id)
+        })
+    }
+
+    /// Iterates over all directories in depth-first order, starting from the root
+    pub fn directories(&self) -> impl Iterator<Item = EntryId> + '_ {
+        self.iter().filter(|&id| self.entry(id).is_dir())
+    }
+
+    /// Returns the number of directories between the root and an entry, 0 for the root
+    #[must_use]
+    pub fn depth(&self, id: EntryId) -> usize {
+        std::iter::successors(self.entry(id).parent, |&parent| self.entry(parent).parent).count()
+    }
+
+    /// Draws the filesystem like the `tree` command, with human-readable sizes
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use advent_of_code_2022::day_7::FileSystem;
+    ///
+    /// let fs = FileSystem::from_transcript("$ ls\ndir a\n2048 b\n$ cd a\n$ ls\n10 c");
+    ///
+    /// assert_eq!(fs.tree(), "/ (2.0K)\n├── a (10B)\n│   └── c (10B)\n└── b (2.0K)\n");
+    /// ```
+    #[must_use]
+    pub fn tree(&self) -> String {
+        let mut tree = format!("/ ({})\n", human_size(self.size(self.root())));
+        self.write_tree(self.root(), "", &mut tree);
+        tree
+    }
+
+    fn write_tree(&self, dir: EntryId, prefix: &str, tree: &mut String) {
+        let children = self.entry(dir).children();
+
+        for (idx, &child) in children.iter().enumerate() {
+            let (branch, indent) = if idx + 1 == children.len() {
+                ("└── ", "    ")
+            } else {
+                ("├── ", "│   ")
+            };
+
+            let entry = self.entry(child);
+            // Writing to a String cannot fail
+            let _ = writeln!(
+                tree,
+                "{prefix}{branch}{} ({})",
+                entry.name,
+                human_size(self.size(child))
+            );
+
+            if entry.is_dir() {
+                self.write_tree(child, &format!("{prefix}{indent}"), tree);
+            }
+        }
+    }
+
+    /// Returns the directories up to `max_depth` below the root, in the order `du -d N` prints
+    /// them: subdirectories before their parent
+    #[must_use]
+    pub fn du(&self, max_depth: usize) -> Vec<EntryId> {
+        let mut stack = vec![(self.root(), 0)];
+        let mut directories = vec![];
+
+        // Parents come before their children, which are reversed, so we reverse the whole list
+        while let Some((dir, depth)) = stack.pop() {
+            directories.push(dir);
+
+            if depth < max_depth {
+                stack.extend(
+                    self.entry(dir)
+                        .children()
+                        .iter()
+                        .filter(|&&child| self.entry(child).is_dir())
+                        .map(|&child| (child, depth + 1)),
+                );
+            }
+        }
+
+        directories.reverse();
+        directories
+    }
+
+    /// Returns the entries matching all predicates, in depth-first order
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use advent_of_code_2022::day_7::{FileSystem, Predicate};
+    ///
+    /// let fs = FileSystem::from_transcript("$ ls\ndir a\n20 b.txt\n$ cd a\n$ ls\n10 c.txt\n30 d");
+    /// let found = fs.find(&[Predicate::Name("*.txt".to_string()), Predicate::SizeAtMost(15)]);
+    ///
+    /// assert_eq!(found.iter().map(|&id| fs.path(id)).collect::<Vec<_>>(), ["/a/c.txt"]);
+    /// ```
+    #[must_use]
+    pub fn find(&self, predicates: &[Predicate]) -> Vec<EntryId> {
+        self.iter()
+            .filter(|&id| {
+                predicates.iter().all(|predicate| match predicate {
+                    Predicate::SizeAtLeast(size) => self.size(id) >= *size,
+                    Predicate::SizeAtMost(size) => self.size(id) <= *size,
+                    Predicate::Name(pattern) => glob_match(pattern, &self.entry(id).name),
+                    Predicate::Directory => self.entry(id).is_dir(),
+                    Predicate::File => !self.entry(id).is_dir(),
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the `n` largest directories, largest first
+    #[must_use]
+    pub fn largest_directories(&self, n: usize) -> Vec<EntryId> {
+        self.largest(self.directories(), n)
+    }
+
+    /// Returns the `n` largest files, largest first
+    #[must_use]
+    pub fn largest_files(&self, n: usize) -> Vec<EntryId> {
+        self.largest(self.iter().filter(|&id| !self.entry(id).is_dir()), n)
+    }
+
+    // Entries of the same size stay in depth-first order
+    fn largest(&self, entries: impl Iterator<Item = EntryId>, n: usize) -> Vec<EntryId> {
+        let mut entries: Vec<EntryId> = entries.collect();
+        entries.sort_by_key(|&id| Reverse(self.size(id)));
+        entries.truncate(n);
+        entries
+    }
+
+    /// Finds the deletions that free `required_space` with the smallest total deleted size.
+    ///
+    /// Any file or directory can be deleted, the root included, but chosen entries are never
+    /// nested in each other. At most `max_items` entries are deleted, and among plans deleting the
+    /// same size the one with the fewest entries wins.
+    ///
+    /// This is an exact knapsack searched one deletion at a time: each round keeps every total
+    /// below the needed space reachable with one more deletion, unless it was already reachable
+    /// with fewer. It stops as soon as a plan deletes exactly the needed space or a round reaches
+    /// no new total, but still runs in O(entries * `max_items` * needed space) in the worst case.
+    ///
+    /// # Arguments
+    /// * `total_space` - The size of the disk
+    /// * `required_space` - The free space we need
+    /// * `max_items` - The maximum number of entries we can delete
+    ///
+    /// # Returns
+    /// `None` if no plan of at most `max_items` deletions frees enough space
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use advent_of_code_2022::day_7::FileSystem;
+    ///
+    /// let fs = FileSystem::from_transcript("$ ls\ndir a\n30 b\n$ cd a\n$ ls\n50 c\n25 d");
+    ///
+    /// // The disk is full, "/a/d" and "/b" free exactly the 55 we need
+    /// let plan = fs.plan_deletion(105, 55, 2).unwrap();
+    /// assert_eq!(plan.paths, ["/a/d", "/b"]);
+    /// assert_eq!(plan.deleted_size, 55);
+    /// assert_eq!(plan.free_space, 55);
+    ///
+    /// // With one deletion, we have to delete the whole "/a" directory
+    /// assert_eq!(fs.plan_deletion(105, 55, 1).unwrap().paths, ["/a"]);
+    /// assert_eq!(fs.plan_deletion(105, 55, 0), None);
+    /// ```
+    #[must_use]
+    pub fn plan_deletion(
+        &self,
+        total_space: usize,
+        required_space: usize,
+        max_items: usize,
+    ) -> Option<DeletionPlan> {
+        let free_space = total_space.saturating_sub(self.size(self.root()));
+
+        // The space we still need to free
+        let target = required_space.saturating_sub(free_space);
+
+        if target == 0 {
+            return Some(DeletionPlan {
+                paths: vec![],
+                deleted_size: 0,
+                free_space,
+            });
+        }
+
+        // Entries in depth-first order, where ends[i] is the position right after the subtree of
+        // the i-th entry: deleting it means skipping to that position
+        let entries: Vec<EntryId> = self.iter().collect();
+        let mut positions = vec![0; self.entries.len()];
+        for (position, id) in entries.iter().enumerate() {
+            positions[id.0] = position;
+        }
+
+        let mut ends: Vec<usize> = (1..=entries.len()).collect();
+        for (position, id) in entries.iter().enumerate().rev() {
+            if let Some(parent) = self.entry(*id).parent {
+                ends[positions[parent.0]] = ends[positions[parent.0]].max(ends[position]);
+            }
+        }
+
+        // levels[count] holds the totals below the target reached with `count` deletions, with
+        // the first position they can be extended from and how they were reached. The empty plan
+        // starts it all, its entry is never read.
+        let mut levels: Vec<HashMap<usize, Birth>> = vec![HashMap::from([(
+            0,
+            Birth {
+                position: 0,
+                entry: self.root(),
+                previous: 0,
+            },
+        )])];
+        // The first position each total can be extended from, over all levels so far: reaching it
+        // again later with more deletions is useless
+        let mut earliest: HashMap<usize, usize> = HashMap::from([(0, 0)]);
+        // Best plan as (deleted size, deletions count, last entry, total before it)
+        let mut best: Option<(usize, usize, EntryId, usize)> = None;
+
+        // We can never delete more entries than there are
+        for count in 0..max_items.min(entries.len()) {
+            // Sorting keeps the chosen plan deterministic on ties
+            let mut totals: Vec<(usize, usize)> = levels[count]
+                .iter()
+                .map(|(&total, birth)| (birth.position, total))
+                .collect();
+            totals.sort_unstable_by_key(|&(position, total)| (Reverse(position), total));
+
+            // The last deletion is the smallest entry after the total's position that completes
+            // it, so we find it with the entries after that position sorted by size
+            let mut available = BTreeSet::new();
+            let mut next_position = entries.len();
+
+            for &(position, total) in &totals {
+                while next_position > position {
+                    next_position -= 1;
+                    available.insert((self.size(entries[next_position]), next_position));
+                }
+
+                if let Some(&(size, last)) = available.range((target - total, 0)..).next() {
+                    let deleted = total + size;
+
+                    if best.is_none_or(|(best_deleted, best_count, _, _)| {
+                        (deleted, count + 1) < (best_deleted, best_count)
+                    }) {
+                        best = Some((deleted, count + 1, entries[last], total));
+                    }
+                }
+            }
+
+            // Nothing can beat deleting exactly the needed space, and later levels use more items
+            if best.is_some_and(|(deleted, _, _, _)| deleted == target) || count + 1 == max_items {
+                break;
+            }
+
+            let mut next_level: HashMap<usize, Birth> = HashMap::new();
+
+            for &(position, total) in &totals {
+                for (last, &id) in entries.iter().enumerate().skip(position) {
+                    let deleted = total + self.size(id);
+                    let end = ends[last];
+
+                    if deleted >= target || earliest.get(&deleted).is_some_and(|&e| e <= end) {
+                        continue;
+                    }
+
+                    earliest.insert(deleted, end);
+                    next_level.insert(
+                        deleted,
+                        Birth {
+                            position: end,
+                            entry: id,
+                            previous: total,
+                        },
+                    );
+                }
+            }
+
+            // No new total means more deletions cannot find anything else
+            if next_level.is_empty() {
+                break;
+            }
+            levels.push(next_level);
+        }
+
+        let (deleted_size, count, last, mut total) = best?;
+
+        // We follow the births back to an empty plan
+        let mut chosen = vec![last];
+        for count in (1..count).rev() {
+            let birth = &levels[count][&total];
+            chosen.push(birth.entry);
+            total = birth.previous;
+        }
+        chosen.reverse();
+
+        Some(DeletionPlan {
+            paths: chosen.into_iter().map(|id| self.path(id)).collect(),
+            deleted_size,
+            free_space: free_space + deleted_size,
+        })
+    }
+
+    /// Exports the filesystem as JSON, with nested directories and sizes
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use advent_of_code_2022::day_7::FileSystem;
+    ///
+    /// let fs = FileSystem::from_transcript("$ ls\ndir a\n10 \"b\"");
+    ///
+    /// assert_eq!(
+    ///     fs.to_json(),
+    ///     r#"{"name":"/","type":"directory","size":10,"children":[{"name":"a","type":"directory","size":0,"children":[]},{"name":"\"b\"","type":"file","size":10}]}"#
+    /// );
+    /// ```
+    #[must_use]
+    pub fn to_json(&self) -> String {
+        let mut json = String::new();
+        self.write_json(self.root(), &mut json);
+        json
+    }
+
+    fn write_json(&self, id: EntryId, json: &mut String) {
+        let entry = self.entry(id);
+        let kind = if entry.is_dir() { "directory" } else { "file" };
+
+        // Writing to a String cannot fail
+        let _ = write!(
+            json,
+            r#"{{"name":{},"type":"{kind}","size":{}"#,
+            json_string(&entry.name),
+            self.size(id)
+        );
+
+        if entry.is_dir() {
+            json.push_str(r#","children":["#);
+
+            for (idx, &child) in entry.children().iter().enumerate() {
+                if idx > 0 {
+                    json.push(',');
+                }
+                self.write_json(child, json);
+            }
+
+            json.push(']');
+        }
+
+        json.push('}');
+    }
+
+    /// Creates the filesystem's directories and files under `dir`.
+    ///
+    /// Files are created with the right size but no content, so they are sparse on filesystems
+    /// that support it and take almost no disk space.
+    ///
+    /// # Errors
+    /// Returns the first IO error, for example if a file already exists as a directory, or an
+    /// `InvalidInput` error if an entry name is not a single normal path component
+    pub fn materialise(&self, dir: &Path) -> io::Result<()> {
+        for id in self.iter() {
+            let path = dir.join(self.relative_path(id)?);
+
+            match self.entry(id).kind {
+                EntryKind::Directory { .. } => fs::create_dir_all(path)?,
+                EntryKind::File { size } => fs::File::create(path)?.set_len(size as u64)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    // Path of an entry relative to the root, refusing any name that could leave the root
+    fn relative_path(&self, id: EntryId) -> io::Result<PathBuf> {
+        let mut names = vec![];
+        let mut current = id;
+
+        while let Some(parent) = self.entry(current).parent {
+            let name = &self.entry(current).name;
+            let mut components = Path::new(name).components();
+
+            match (components.next(), components.next()) {
+                (Some(Component::Normal(component)), None) if component == name.as_str() => {
+                    names.push(name);
+                }
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("Invalid entry name: {name:?}"),
+                    ))
+                }
+            }
+
+            current = parent;
+        }
+
+        Ok(names.into_iter().rev().collect())
+    }
+
+    /// Creates the filesystem in a new directory of the system temp directory, and returns the
+    /// path of that directory. Deleting it is up to the caller.
+    ///
+    /// # Errors
+    /// Returns the first IO error, see [`FileSystem::materialise`]
+    pub fn materialise_in_temp_dir(&self) -> io::Result<PathBuf> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_nanos());
+        let dir = env::temp_dir().join(format!(
+            "advent_of_code_2022_day_7_{}_{nanos}_{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        // Fails if the directory already exists, so we never write in someone else's directory
+        fs::create_dir(&dir)?;
+        self.materialise(&dir)?;
+
+        Ok(dir)
+    }
 }
 
-fn get_current_dir_name(current_dir: &[String]) -> String {
-    current_dir
-        .iter()
-        .skip(1) // Skipping the / at the beginning of all
-        .fold(String::new(), |acc, x| acc + "/" + x)
+/// Formats a string as a JSON string, quotes included
+fn json_string(s: &str) -> String {
+    let mut json = String::from('"');
+
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(json, "\\u{:04x}", c as u32);
+            }
+            c => json.push(c),
+        }
+    }
+
+    json.push('"');
+    json
 }
 
-/// Gets the sum of the sizes of all directories that are less than `max_size`
-#[must_use] pub fn get_sum_of_small_dir_sizes(input: &str, max_size: usize) -> usize {
-    let directories = get_directories(input);
+// How a total was reached in the deletion planner
+#[derive(Debug, Clone, Copy)]
+struct Birth {
+    position: usize,
+    entry: EntryId,
+    previous: usize,
+}
 
-    // We check all directories and fold them into a result
-    directories.keys().fold(0, |result, dir_name| {
-        // We get the directory size by recursively checking its children
-        let size = directories
-            .iter()
-            .filter(|(name, _)| name.starts_with(dir_name))
-            .fold(0, |size, (_, child_size)| size + child_size);
+/// The result of [`FileSystem::plan_deletion`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeletionPlan {
+    /// Paths of the deleted entries, in depth-first order
+    pub paths: Vec<String>,
+    pub deleted_size: usize,
+    /// Free space after the deletions
+    pub free_space: usize,
+}
 
-        if size <= max_size {
-            result + size
-        } else {
-            result
+/// A condition on entries for [`FileSystem::find`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Predicate {
+    SizeAtLeast(usize),
+    SizeAtMost(usize),
+    /// The name matches a glob pattern, where `*` is any text and `?` any character
+    Name(String),
+    Directory,
+    File,
+}
+
+/// Formats a size like `ls -h`, with 1024-based units: `584B`, `9.3K`, `92K`, `23M`
+#[must_use]
+pub fn human_size(size: usize) -> String {
+    const UNITS: [&str; 5] = ["K", "M", "G", "T", "P"];
+
+    if size < 1024 {
+        return format!("{size}B");
+    }
+
+    let mut value = size as f64 / 1024.0;
+    let mut unit = 0;
+
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if value < 10.0 {
+        format!("{value:.1}{}", UNITS[unit])
+    } else {
+        format!("{value:.0}{}", UNITS[unit])
+    }
+}
+
+/// Matches a whole name against a glob pattern, where `*` is any text and `?` any character
+#[must_use]
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    let (mut p, mut n) = (0, 0);
+    // Position of the last star in the pattern, and of the name text it currently matches up to
+    let mut star = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            // On a mismatch, the last star takes one more character
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    star = Some((star_p, star_n + 1));
+                    p = star_p + 1;
+                    n = star_n + 1;
+                }
+                None => return false,
+            },
         }
-    })
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Gets a hashmap of path -> size of the files directly in the directory
+///
+/// Only directories holding files are listed, and the root is keyed as `""`. Sizes do not
+/// include subdirectories, see [`get_directory_sizes`] for total sizes.
+#[must_use]
+pub fn get_directories(input: &str) -> HashMap<String, usize> {
+    let fs = FileSystem::from_transcript(input);
+
+    fs.directories()
+        .filter_map(|dir| {
+            let mut files = fs
+                .entry(dir)
+                .children()
+                .iter()
+                .filter(|&&child| !fs.entry(child).is_dir())
+                .peekable();
+            files.peek()?;
+
+            // Legacy keys: the root is "" and the others start with "/"
+            let path = if dir == fs.root() {
+                String::new()
+            } else {
+                fs.path(dir)
+            };
+
+            Some((path, files.map(|&file| fs.size(file)).sum()))
+        })
+        .collect()
+}
+
+/// Gets a hashmap of path -> total size of the directory, subdirectories included
+///
+/// # Examples
+///
+/// ```
+/// use advent_of_code_2022::day_7::get_directory_sizes;
+///
+/// let sizes = get_directory_sizes("$ cd /\n$ ls\ndir a\ndir ab\n$ cd a\n$ ls\n1 x\n$ cd ..\n$ cd ab\n$ ls\n10 y");
+///
+/// assert_eq!(sizes["/"], 11);
+/// assert_eq!(sizes["/a"], 1);
+/// assert_eq!(sizes["/ab"], 10);
+/// ```
+#[must_use]
+pub fn get_directory_sizes(input: &str) -> HashMap<String, usize> {
+    let fs = FileSystem::from_transcript(input);
+
+    fs.directories()
+        .map(|dir| (fs.path(dir), fs.size(dir)))
+        .collect()
+}
+
+/// Gets the sum of the sizes of all directories that are less than `max_size`
+#[must_use]
+pub fn get_sum_of_small_dir_sizes(input: &str, max_size: usize) -> usize {
+    let fs = FileSystem::from_transcript(input);
+
+    fs.directories()
+        .map(|dir| fs.size(dir))
+        .filter(|&size| size <= max_size)
+        .sum()
 }
 
-#[must_use] pub fn get_smallest_valid_folder_size(
+#[must_use]
+pub fn get_smallest_valid_folder_size(
     input: &str,
     total_space: usize,
     required_space: usize,
 ) -> usize {
-    let directories = get_directories(input);
+    let fs = FileSystem::from_transcript(input);
 
-    let total_used_space: usize = directories.values().sum();
+    let total_used_space = fs.size(fs.root());
 
-    // We check all directories and fold them into a result
-    directories
-        .keys()
-        // We first compute the size
-        .map(|dir_name| {
-            // We get the directory size by recursively checking its children
-            directories
-                .iter()
-                .filter(|(name, _)| name.starts_with(dir_name))
-                .fold(0, |size, (_, child_size)| size + child_size)
-        })
+    fs.directories()
+        .map(|dir| fs.size(dir))
         .filter(|size| {
             // total_used_space - current_dir_size = size used after potential deletion
             total_used_space - size 
@@ -113,39 +1072,175 @@ fn get_current_dir_name(current_dir: &[String]) -> String {
         .expect("No suitable directory found")
 }
 
-#[derive(PartialEq, Debug)]
+/// An error in a shell transcript, with its line number (starting at 1)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TranscriptError {
+    /// The command is not a `cd`, `ls` or `pwd` we understand
+    UnknownCommand { line: usize, command: String },
+    /// The line is not a valid `ls` output line
+    InvalidOutput { line: usize, output: String },
+    /// The line is output, but the previous command prints nothing
+    UnexpectedOutput { line: usize, output: String },
+    /// `pwd` printed another directory than the one we are in
+    WrongDirectory {
+        line: usize,
+        expected: String,
+        found: String,
+    },
+    /// `cd ..` was used in the root directory
+    MoveAboveRoot { line: usize },
+    /// A file is used as a directory
+    NotADirectory { line: usize, path: String },
+    /// A directory is listed as a file
+    NotAFile { line: usize, path: String },
+}
+
+impl Display for TranscriptError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownCommand { line, command } => {
+                write!(f, "Line {line}: unknown command {command:?}")
+            }
+            Self::InvalidOutput { line, output } => {
+                write!(f, "Line {line}: cannot parse ls output {output:?}")
+            }
+            Self::UnexpectedOutput { line, output } => {
+                write!(f, "Line {line}: unexpected output {output:?}")
+            }
+            Self::WrongDirectory {
+                line,
+                expected,
+                found,
+            } => write!(f, "Line {line}: pwd printed {found}, expected {expected}"),
+            Self::MoveAboveRoot { line } => write!(f, "Line {line}: cannot move above the root"),
+            Self::NotADirectory { line, path } => {
+                write!(f, "Line {line}: {path} is not a directory")
+            }
+            Self::NotAFile { line, path } => write!(f, "Line {line}: {path} is not a file"),
+        }
+    }
+}
+
+impl std::error::Error for TranscriptError {}
+
+#[derive(PartialEq, Debug, Clone)]
 enum Command {
     CD(CommandCD),
     LS,
+    Pwd,
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone)]
 enum CommandCD {
     Path(String),
     Backwards,
 }
 
+/// Nom parser for a command, without the leading `$ `
 fn nom_command(cmd: &str) -> IResult<&str, Command> {
-    let (folder, (cmd, _)) = tuple((alpha1, space0))(cmd)?;
-
-    match cmd {
-        "cd" => match folder {
-            ".." => Ok(("", Command::CD(CommandCD::Backwards))),
-            _ => Ok(("", Command::CD(CommandCD::Path(folder.to_string())))),
+    let cd = map(
+        preceded(pair(tag("cd"), space1), is_not(" \t")),
+        |path: &str| {
+            Command::CD(match path {
+                ".." => CommandCD::Backwards,
+                _ => CommandCD::Path(path.to_string()),
+            })
         },
-        "ls" => Ok(("", Command::LS)),
-        _ => panic!("Unknown command"),
-    }
+    );
+    // Flags only change the output format, which we detect line by line
+    let ls = value(
+        Command::LS,
+        pair(
+            tag("ls"),
+            many0(preceded(space1, preceded(char('-'), alpha1))),
+        ),
+    );
+    let pwd = value(Command::Pwd, tag("pwd"));
+
+    all_consuming(terminated(alt((cd, ls, pwd)), space0))(cmd)
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+enum ListedEntry<'a> {
+    Directory(&'a str),
+    File(&'a str, usize),
+    /// The `total N` line of `ls -l`
+    Total,
+    /// An `ls -l` line we don't track: `.`, `..`, symlinks, devices, sockets and pipes
+    Skipped,
+}
+
+/// Names can hold anything but whitespace and slashes, like `b-1.tar.gz`, but cannot be `.` or `..`
+fn entry_name(s: &str) -> IResult<&str, &str> {
+    verify(is_not(" \t/"), |name: &str| name != "." && name != "..")(s)
+}
+
+fn size(s: &str) -> IResult<&str, usize> {
+    map_res(digit1, str::parse::<usize>)(s)
 }
 
-fn parse_command(cmd: &str) -> Command {
-    let (_, command) = nom_command(cmd).expect("Cannot parse command");
-    command
+/// Nom parser for a line of `ls` output: `dir a`, `123 b.txt` or an `ls -l` line
+fn nom_listed_entry(line: &str) -> IResult<&str, ListedEntry<'_>> {
+    alt((
+        map(preceded(tag("dir "), entry_name), ListedEntry::Directory),
+        map(separated_pair(size, space1, entry_name), |(size, name)| {
+            ListedEntry::File(name, size)
+        }),
+        value(ListedEntry::Total, pair(tag("total "), size)),
+        long_listed_entry,
+    ))(line)
+}
+
+/// Nom parser for an `ls -l` line, like `-rw-r--r-- 1 user group 1234 Jan  1 12:00 b.txt`
+fn long_listed_entry(line: &str) -> IResult<&str, ListedEntry<'_>> {
+    let permissions = || terminated(count(one_of("rwxsStT-"), 9), opt(one_of(".@+")));
+
+    // Other file types have no size we care about, devices even show `major, minor` instead
+    let skipped = value(
+        ListedEntry::Skipped,
+        tuple((one_of("lcbps"), permissions(), space1, rest)),
+    );
+
+    alt((skipped, regular_long_listed_entry))(line)
+}
+
+/// Nom parser for an `ls -l` line of a directory or a regular file
+fn regular_long_listed_entry(line: &str) -> IResult<&str, ListedEntry<'_>> {
+    let field = || terminated(is_not(" \t"), space1);
+
+    let (rest, (kind, _, _, _links, _user, _group, size, _month, _day, _time)) = tuple((
+        one_of("d-"),
+        terminated(count(one_of("rwxsStT-"), 9), opt(one_of(".@+"))),
+        space1,
+        field(),
+        field(),
+        field(),
+        terminated(size, space1),
+        field(),
+        field(),
+        field(),
+    ))(line)?;
+    let (rest, name) = is_not(" \t/")(rest)?;
+
+    // `ls -la` lists the current and parent directories too
+    let entry = match kind {
+        'd' if name == "." || name == ".." => ListedEntry::Skipped,
+        'd' => ListedEntry::Directory(name),
+        _ => ListedEntry::File(entry_name(name)?.1, size),
+    };
+
+    Ok((rest, entry))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_utils::Lcg;
+
+    fn parse_command(cmd: &str) -> Command {
+        let (_, command) = nom_command(cmd).expect("Cannot parse command");
+        command
+    }
 
     #[test]
     fn test_parse_command() {
@@ -162,15 +1257,184 @@ mod tests {
     }
 
     #[test]
-    fn test_print_current_dir() {
+    fn test_parse_richer_commands() {
+        assert_eq!(
+            parse_command("cd /a/b-1/../c.d"),
+            Command::CD(CommandCD::Path("/a/b-1/../c.d".to_string()))
+        );
+        assert_eq!(parse_command("ls -l -a"), Command::LS);
+        assert_eq!(parse_command("ls -la "), Command::LS);
+        assert_eq!(parse_command("pwd"), Command::Pwd);
+
+        assert!(nom_command("lsx").is_err());
+        assert!(nom_command("cd").is_err());
+        assert!(nom_command("cd a b").is_err());
+        assert!(nom_command("ls a").is_err());
+    }
+
+    #[test]
+    fn test_listed_entries() {
+        let entry = |line| all_consuming(nom_listed_entry)(line).map(|(_, entry)| entry);
+
+        assert_eq!(entry("dir a-1.d"), Ok(ListedEntry::Directory("a-1.d")));
+        assert_eq!(
+            entry("123 b.tar.gz"),
+            Ok(ListedEntry::File("b.tar.gz", 123))
+        );
+        assert_eq!(entry("total 24"), Ok(ListedEntry::Total));
+        assert_eq!(
+            entry("drwxr-xr-x  2 me staff 4096 Jan  1 12:00 src"),
+            Ok(ListedEntry::Directory("src"))
+        );
+        assert_eq!(
+            entry("-rw-r--r--. 1 me staff 1234 Dec 24  2022 main.rs"),
+            Ok(ListedEntry::File("main.rs", 1234))
+        );
+        assert_eq!(
+            entry("drwxr-xr-x  2 me staff 4096 Jan  1 12:00 .."),
+            Ok(ListedEntry::Skipped)
+        );
+        assert_eq!(
+            entry("lrwxrwxrwx 1 me staff 4 Jan 1 12:00 l -> a"),
+            Ok(ListedEntry::Skipped)
+        );
+        assert_eq!(
+            entry("crw-rw---- 1 root tty 4, 0 Jan 1 12:00 tty0"),
+            Ok(ListedEntry::Skipped)
+        );
+        assert!(entry("-rw-r--r-- 1 me staff 4 Jan 1 12:00 ..").is_err());
+        assert!(entry("12").is_err());
+        assert!(entry("file 12").is_err());
+    }
+
+    #[test]
+    fn test_long_listing_transcript() {
+        let fs = FileSystem::parse_transcript(
+            "$ cd /
+$ ls -la
+total 16
+drwxr-xr-x 3 me me 4096 Jan  1 12:00 .
+drwxr-xr-x 9 me me 4096 Jan  1 12:00 ..
+drwxr-xr-x 2 me me 4096 Jan  1 12:00 a
+-rw-r--r-- 1 me me  100 Jan  1 12:00 b.txt
+lrwxrwxrwx 1 me me    5 Jan  1 12:00 c -> b.txt
+crw-rw---- 1 me tty 4, 0 Jan  1 12:00 tty0
+$ cd a
+$ ls -la
+drwxr-xr-x 2 me me 4096 Jan  1 12:00 .
+drwxr-xr-x 3 me me 4096 Jan  1 12:00 ..
+-rw-r--r-- 1 me me   20 Jan  1 12:00 d
+",
+        )
+        .unwrap();
+
+        assert_eq!(fs.size(fs.root()), 120);
+        assert_eq!(fs.lookup("/c"), None);
+        assert_eq!(fs.iter().count(), 4);
+    }
+
+    #[test]
+    fn test_richer_transcript() {
+        let fs = FileSystem::parse_transcript(
+            "$ cd /
+$ ls -l
+total 8
+drwxr-xr-x 2 me me 4096 Jan  1 12:00 a
+-rw-r--r-- 1 me me 100 Jan  1 12:00 b.txt
+$ cd a/new-dir/./../
+$ pwd
+/a
+$ ls
+30 c_1.log
+$ cd /
+$ cd a/new-dir
+$ ls
+5 d
+$ cd /a/new-dir/../..
+$ pwd
+/
+",
+        )
+        .unwrap();
+
+        assert_eq!(fs.size(fs.root()), 135);
+        assert_eq!(fs.lookup("/a").map(|a| fs.size(a)), Some(35));
+        assert_eq!(fs.lookup("/a/new-dir/d").map(|d| fs.size(d)), Some(5));
+        assert_eq!(fs.lookup("/a/c_1.log").map(|c| fs.size(c)), Some(30));
+        assert_eq!(fs.directories().count(), 3);
+    }
+
+    #[test]
+    fn test_transcript_errors() {
+        let error = |input| FileSystem::parse_transcript(input).unwrap_err();
+
+        assert_eq!(
+            error("$ cd /\n$ ls\n$ mkdir a"),
+            TranscriptError::UnknownCommand {
+                line: 3,
+                command: "mkdir a".to_string()
+            }
+        );
+        assert_eq!(
+            error("$ ls\n12"),
+            TranscriptError::InvalidOutput {
+                line: 2,
+                output: "12".to_string()
+            }
+        );
         assert_eq!(
-            get_current_dir_name(&["/".to_string(), "a".to_string()]),
-            "/a"
+            error("$ cd a\n1 b"),
+            TranscriptError::UnexpectedOutput {
+                line: 2,
+                output: "1 b".to_string()
+            }
+        );
+        assert_eq!(
+            error("$ cd a\n$ pwd\n/b"),
+            TranscriptError::WrongDirectory {
+                line: 3,
+                expected: "/a".to_string(),
+                found: "/b".to_string()
+            }
         );
         assert_eq!(
-            get_current_dir_name(&["/".to_string(), "a".to_string(), "b".to_string()]),
-            "/a/b"
+            error("$ cd a/../.."),
+            TranscriptError::MoveAboveRoot { line: 1 }
         );
+        assert_eq!(
+            error("$ ls\n1 a\n$ cd a"),
+            TranscriptError::NotADirectory {
+                line: 3,
+                path: "/a".to_string()
+            }
+        );
+        assert_eq!(
+            error("$ ls\ndir a\n1 a").to_string(),
+            "Line 3: /a is not a file"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Line 2: unknown command \"cat a\"")]
+    fn test_from_transcript_panic() {
+        let _ = FileSystem::from_transcript("$ cd /\n$ cat a");
+    }
+
+    #[test]
+    fn test_get_directories() {
+        let directories = get_directories(DEMO_INPUT);
+
+        assert_eq!(directories[""], 23_352_670);
+        assert_eq!(directories["/a"], 94_269);
+        assert_eq!(directories["/a/e"], 584);
+        assert_eq!(directories["/d"], 24_933_642);
+        assert_eq!(directories.len(), 4);
+
+        // Directories without files of their own are left out
+        let directories =
+            get_directories("$ cd /\n$ ls\ndir a\n$ cd a\n$ ls\ndir b\n$ cd b\n$ ls\n5 x");
+
+        assert_eq!(directories, HashMap::from([("/a/b".to_string(), 5)]));
     }
 
     #[test]
@@ -179,6 +1443,432 @@ mod tests {
         nom_command("test").unwrap();
     }
 
+    #[test]
+    fn test_filesystem() {
+        let fs = FileSystem::from_transcript(DEMO_INPUT);
+
+        assert_eq!(fs.size(fs.root()), 48_381_165);
+        assert_eq!(fs.lookup("/a").map(|a| fs.size(a)), Some(94_853));
+        assert_eq!(fs.lookup("/a/e").map(|e| fs.size(e)), Some(584));
+        assert_eq!(fs.lookup("/d/").map(|d| fs.size(d)), Some(24_933_642));
+        assert_eq!(
+            fs.lookup("/a/e/i").map(|i| fs.entry(i).is_dir()),
+            Some(false)
+        );
+        assert_eq!(fs.lookup("/a/x"), None);
+        assert_eq!(fs.lookup("/"), Some(fs.root()));
+
+        let i = fs.lookup("/a/e/i").unwrap();
+        assert_eq!(fs.path(i), "/a/e/i");
+        assert_eq!(fs.entry(i).name(), "i");
+        assert_eq!(fs.path(fs.root()), "/");
+    }
+
+    #[test]
+    fn test_filesystem_iter() {
+        let fs = FileSystem::from_transcript(DEMO_INPUT);
+
+        let paths: Vec<String> = fs.iter().map(|id| fs.path(id)).collect();
+        assert_eq!(
+            paths,
+            [
+                "/", "/a", "/a/e", "/a/e/i", "/a/f", "/a/g", "/a/h.lst", "/b.txt", "/c.dat", "/d",
+                "/d/j", "/d/d.log", "/d/d.ext", "/d/k"
+            ]
+        );
+
+        let directories: Vec<String> = fs.directories().map(|id| fs.path(id)).collect();
+        assert_eq!(directories, ["/", "/a", "/a/e", "/d"]);
+    }
+
+    #[test]
+    fn test_filesystem_empty_directories() {
+        let fs = FileSystem::from_transcript("$ cd /\n$ ls\ndir empty\n12 a\n$ cd x\n$ ls");
+
+        let empty = fs.lookup("/empty").unwrap();
+        assert!(fs.entry(empty).is_dir());
+        assert_eq!(fs.size(empty), 0);
+        assert_eq!(fs.lookup("/x").map(|x| fs.size(x)), Some(0));
+        assert_eq!(fs.directories().count(), 3);
+    }
+
+    #[test]
+    fn test_filesystem_size_cache() {
+        let mut fs = FileSystem::from_transcript(DEMO_INPUT);
+        let e = fs.lookup("/a/e").unwrap();
+
+        assert_eq!(fs.size(fs.root()), 48_381_165);
+
+        // Adding a file resets the cached sizes of its ancestors
+        fs.add_file(e, "new", 1);
+        assert_eq!(fs.size(e), 585);
+        assert_eq!(fs.size(fs.root()), 48_381_166);
+
+        // Listing a file again replaces its size
+        fs.add_file(e, "i", 84);
+        assert_eq!(fs.size(fs.root()), 48_380_666);
+    }
+
+    #[test]
+    fn test_prefix_siblings() {
+        let sizes = get_directory_sizes(PREFIX_INPUT);
+
+        assert_eq!(sizes["/"], 1_101_011);
+        assert_eq!(sizes["/a"], 100_011);
+        assert_eq!(sizes["/a/b"], 10);
+        assert_eq!(sizes["/ab"], 1_000_000);
+        assert_eq!(sizes["/ab/b"], 100);
+        assert_eq!(sizes["/abc"], 1_000);
+        // Only holds a directory, so it would have no direct file size
+        assert_eq!(sizes["/a/bb"], 100_000);
+        assert_eq!(sizes["/a/bb/c"], 100_000);
+        assert_eq!(sizes.len(), 8);
+
+        // Everything but "/" and "/ab": "/ab" and "/abc" are not part of "/a"
+        assert_eq!(get_sum_of_small_dir_sizes(PREFIX_INPUT, 100_011), 301_121);
+        assert_eq!(
+            get_smallest_valid_folder_size(PREFIX_INPUT, 1_200_000, 100_000),
+            100_000
+        );
+    }
+
+    #[test]
+    fn test_size_cache_is_filled_at_once() {
+        let fs = FileSystem::from_transcript(PREFIX_INPUT);
+
+        assert_eq!(fs.size(fs.lookup("/a/b").unwrap()), 10);
+
+        for dir in fs.directories() {
+            assert!(
+                fs.entry(dir).total_size.get().is_some(),
+                "{} is not cached",
+                fs.path(dir)
+            );
+        }
+    }
+
+    #[test]
+    fn test_human_size() {
+        assert_eq!(human_size(0), "0B");
+        assert_eq!(human_size(1023), "1023B");
+        assert_eq!(human_size(1024), "1.0K");
+        assert_eq!(human_size(94_853), "93K");
+        assert_eq!(human_size(24_933_642), "24M");
+        assert_eq!(human_size(48_381_165), "46M");
+        assert_eq!(human_size(5 << 30), "5.0G");
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*", ""));
+        assert!(glob_match("*.txt", "b.txt"));
+        assert!(glob_match("d.*", "d.log"));
+        assert!(glob_match("?", "a"));
+        assert!(glob_match("a*b*c", "aXbYbZc"));
+        assert!(glob_match("*a*", "banana"));
+        assert!(!glob_match("*.txt", "b.txt.gz"));
+        assert!(!glob_match("?", ""));
+        assert!(!glob_match("a", "ab"));
+        assert!(!glob_match("a*b", "acbd"));
+    }
+
+    #[test]
+    fn test_tree() {
+        let fs = FileSystem::from_transcript(DEMO_INPUT);
+
+        assert_eq!(
+            fs.tree(),
+            "/ (46M)
+├── a (93K)
+│   ├── e (584B)
+│   │   └── i (584B)
+│   ├── f (28K)
+│   ├── g (2.5K)
+│   └── h.lst (61K)
+├── b.txt (14M)
+├── c.dat (8.1M)
+└── d (24M)
+    ├── j (3.9M)
+    ├── d.log (7.7M)
+    ├── d.ext (5.4M)
+    └── k (6.9M)
+"
+        );
+    }
+
+    #[test]
+    fn test_du() {
+        let fs = FileSystem::from_transcript(DEMO_INPUT);
+        let paths = |ids: Vec<EntryId>| ids.into_iter().map(|id| fs.path(id)).collect::<Vec<_>>();
+
+        assert_eq!(paths(fs.du(0)), ["/"]);
+        assert_eq!(paths(fs.du(1)), ["/a", "/d", "/"]);
+        assert_eq!(paths(fs.du(10)), ["/a/e", "/a", "/d", "/"]);
+        assert_eq!(fs.depth(fs.lookup("/a/e/i").unwrap()), 3);
+    }
+
+    #[test]
+    fn test_find_and_largest() {
+        let fs = FileSystem::from_transcript(DEMO_INPUT);
+        let paths = |ids: Vec<EntryId>| ids.into_iter().map(|id| fs.path(id)).collect::<Vec<_>>();
+
+        assert_eq!(
+            paths(fs.find(&[Predicate::Directory, Predicate::SizeAtMost(100_000)])),
+            ["/a", "/a/e"]
+        );
+        assert_eq!(
+            paths(fs.find(&[Predicate::File, Predicate::SizeAtLeast(8_000_000)])),
+            ["/b.txt", "/c.dat", "/d/d.log"]
+        );
+        assert_eq!(
+            paths(fs.find(&[Predicate::Name("d*".to_string())])),
+            ["/d", "/d/d.log", "/d/d.ext"]
+        );
+
+        assert_eq!(paths(fs.largest_directories(2)), ["/", "/d"]);
+        assert_eq!(paths(fs.largest_files(3)), ["/b.txt", "/c.dat", "/d/d.log"]);
+        assert_eq!(fs.largest_files(100).len(), 10);
+    }
+
+    #[test]
+    fn test_plan_deletion() {
+        let fs = FileSystem::from_transcript(DEMO_INPUT);
+
+        // Files count too, so "/c.dat" beats "/d"
+        assert_eq!(
+            fs.plan_deletion(70_000_000, 30_000_000, 1),
+            Some(DeletionPlan {
+                paths: vec!["/c.dat".to_string()],
+                deleted_size: 8_504_156,
+                free_space: 30_122_991,
+            })
+        );
+        assert_eq!(fs.plan_deletion(70_000_000, 30_000_000, 0), None);
+        assert_eq!(
+            fs.plan_deletion(70_000_000, 20_000_000, 3),
+            Some(DeletionPlan {
+                paths: vec![],
+                deleted_size: 0,
+                free_space: 21_618_835,
+            })
+        );
+        // Huge counts are capped by the number of entries
+        assert_eq!(
+            fs.plan_deletion(70_000_000, 30_000_000, usize::MAX),
+            fs.plan_deletion(70_000_000, 30_000_000, 1)
+        );
+        // Only deleting everything frees enough
+        assert_eq!(
+            fs.plan_deletion(48_381_165, 48_381_165, 5)
+                .map(|plan| plan.paths),
+            Some(vec!["/".to_string()])
+        );
+
+        let fs = FileSystem::from_transcript(PREFIX_INPUT);
+
+        assert_eq!(
+            fs.plan_deletion(1_101_011, 101_012, 2)
+                .map(|plan| plan.paths),
+            Some(vec!["/ab/x".to_string()])
+        );
+        assert_eq!(
+            fs.plan_deletion(1_101_011, 101_012, 3),
+            Some(DeletionPlan {
+                paths: vec!["/a/bb".to_string(), "/ab/b".to_string(), "/abc".to_string()],
+                deleted_size: 101_100,
+                free_space: 101_100,
+            })
+        );
+    }
+
+    // Smallest deletion, with its count, by trying every set of non-nested entries
+    fn brute_force_deletion(
+        fs: &FileSystem,
+        entries: &[EntryId],
+        target: usize,
+        max_items: usize,
+    ) -> Option<(usize, usize)> {
+        if target == 0 {
+            return Some((0, 0));
+        }
+        if max_items == 0 || entries.is_empty() {
+            return None;
+        }
+
+        let id = entries[0];
+        let subtree = entries
+            .iter()
+            .take_while(|&&other| {
+                std::iter::successors(Some(other), |&e| fs.entry(e).parent).any(|e| e == id)
+            })
+            .count();
+
+        let taken = brute_force_deletion(
+            fs,
+            &entries[subtree..],
+            target.saturating_sub(fs.size(id)),
+            max_items - 1,
+        )
+        .map(|(deleted, count)| (deleted + fs.size(id), count + 1));
+        let skipped = brute_force_deletion(fs, &entries[1..], target, max_items);
+
+        taken.into_iter().chain(skipped).min()
+    }
+
+    #[test]
+    fn test_plan_deletion_brute_force() {
+        // Small pseudo-random filesystem
+        let mut lcg = Lcg::new(17);
+        let mut random = |modulo: usize| lcg.below(modulo);
+
+        let mut transcript = String::new();
+        let mut depth = 0;
+        for dir in 0..6 {
+            transcript += &format!(
+                "$ ls\ndir d{dir}\n{} f{dir}\n$ cd d{dir}\n$ ls\n",
+                random(500)
+            );
+            depth += 1;
+            for file in 0..random(3) {
+                transcript += &format!("{} g{file}\n", random(500));
+            }
+            // Going back up, but never above the root
+            for _ in 0..random(3).min(depth) {
+                transcript += "$ cd ..\n";
+                depth -= 1;
+            }
+        }
+
+        let fs = FileSystem::from_transcript(&transcript);
+        let entries: Vec<EntryId> = fs.iter().collect();
+        let used_space = fs.size(fs.root());
+
+        for max_items in (0..5).chain([usize::MAX]) {
+            for target in (0..used_space).step_by(97) {
+                let plan = fs.plan_deletion(used_space, target, max_items);
+
+                assert_eq!(
+                    plan.as_ref()
+                        .map(|plan| (plan.deleted_size, plan.paths.len())),
+                    brute_force_deletion(&fs, &entries, target, max_items),
+                    "target {target}, {max_items} items"
+                );
+
+                if let Some(plan) = plan {
+                    let chosen: Vec<EntryId> = plan
+                        .paths
+                        .iter()
+                        .filter_map(|path| fs.lookup(path))
+                        .collect();
+                    assert_eq!(
+                        chosen.iter().map(|&id| fs.size(id)).sum::<usize>(),
+                        plan.deleted_size
+                    );
+                    assert_eq!(plan.free_space, plan.deleted_size);
+                    assert!(chosen
+                        .iter()
+                        .all(|&id| chosen.iter().all(|&other| id == other
+                            || !std::iter::successors(fs.entry(id).parent, |&e| fs
+                                .entry(e)
+                                .parent)
+                            .any(|e| e == other))));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_json_string() {
+        assert_eq!(json_string("a.txt"), r#""a.txt""#);
+        assert_eq!(json_string("a\"b\\c"), r#""a\"b\\c""#);
+        assert_eq!(json_string("\t\u{1}é"), r#""\t\u0001é""#);
+    }
+
+    #[test]
+    fn test_to_json() {
+        let fs = FileSystem::from_transcript(DEMO_INPUT);
+        let json = fs.to_json();
+
+        assert!(json.starts_with(
+            r#"{"name":"/","type":"directory","size":48381165,"children":[{"name":"a","type":"directory","size":94853,"children":[{"name":"e","type":"directory","size":584,"children":[{"name":"i","type":"file","size":584}]},"#
+        ));
+        assert!(json.ends_with(r#"{"name":"k","type":"file","size":7214296}]}]}"#));
+        assert_eq!(json.matches(r#""type":"file""#).count(), 10);
+        assert_eq!(json.matches('{').count(), json.matches('}').count());
+        assert_eq!(json.matches('[').count(), json.matches(']').count());
+    }
+
+    // Total size of the files under a directory on disk
+    fn disk_size(path: &Path) -> usize {
+        fs::read_dir(path)
+            .unwrap()
+            .map(|entry| {
+                let entry = entry.unwrap();
+                let metadata = entry.metadata().unwrap();
+
+                if metadata.is_dir() {
+                    disk_size(&entry.path())
+                } else {
+                    metadata.len() as usize
+                }
+            })
+            .sum()
+    }
+
+    #[test]
+    fn test_materialise() {
+        let fs = FileSystem::from_transcript(PREFIX_INPUT);
+        let dir = fs.materialise_in_temp_dir().unwrap();
+
+        for (path, size) in get_directory_sizes(PREFIX_INPUT) {
+            let on_disk = dir.join(path.trim_start_matches('/'));
+            assert_eq!(disk_size(&on_disk), size, "{path}");
+        }
+        assert_eq!(fs::metadata(dir.join("a/bb/c/z")).unwrap().len(), 100_000);
+
+        // Materialising twice in the same place only overwrites files
+        fs.materialise(&dir).unwrap();
+        assert_eq!(disk_size(&dir), 1_101_011);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_names_cannot_escape() {
+        assert!(FileSystem::parse_transcript("$ ls\ndir ..").is_err());
+        assert!(FileSystem::parse_transcript("$ ls\n10 a/b").is_err());
+        assert!(FileSystem::parse_transcript("$ ls\n10 .").is_err());
+
+        for name in ["", ".", "..", "../escaped", "a/b"] {
+            let added = std::panic::catch_unwind(|| {
+                let mut fs = FileSystem::new();
+                fs.add_file(fs.root(), name, 3);
+            });
+            assert!(added.is_err(), "{name:?} was added");
+        }
+    }
+
+    #[test]
+    fn test_materialise_refuses_escaping_names() {
+        let mut fs = FileSystem::new();
+        let a = fs.add_directory(fs.root(), "a");
+        // Sneaking past add_entry's checks, materialise must still refuse it
+        let escaped = fs.add_file(a, "escaped", 3);
+        fs.entries[escaped.0].name = "../../escaped".to_string();
+
+        let dir = env::temp_dir().join(format!(
+            "advent_of_code_2022_day_7_escape_{}",
+            std::process::id()
+        ));
+        fs::create_dir(&dir).unwrap();
+
+        let err = fs.materialise(&dir).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(!dir.join("../escaped").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
     #[test]
     fn test_first_part() {
         assert_eq!(get_sum_of_small_dir_sizes(DEMO_INPUT, 100_000), 95_437);
@@ -215,4 +1905,37 @@ $ ls
 8033020 d.log
 5626152 d.ext
 7214296 k";
+    const PREFIX_INPUT: &str = "$ cd /
+$ ls
+dir a
+dir ab
+dir abc
+$ cd a
+$ ls
+1 x
+dir b
+dir bb
+$ cd b
+$ ls
+10 y
+$ cd ..
+$ cd bb
+$ ls
+dir c
+$ cd c
+$ ls
+100000 z
+$ cd /
+$ cd ab
+$ ls
+999900 x
+dir b
+$ cd b
+$ ls
+100 y
+$ cd ..
+$ cd ..
+$ cd abc
+$ ls
+1000 x";
 }
@@ -1,109 +1,191 @@
+/// Counts the trees visible from outside the forest
+///
+/// # Examples
+///
+/// ```
+/// use advent_of_code_2022::day_8::get_visible_trees_count;
+///
+/// assert_eq!(get_visible_trees_count("30373\n25512\n65332\n33549\n35390"), 21);
+/// ```
 #[must_use]
 pub fn get_visible_trees_count(input: &str) -> usize {
-    let tree_map = create_tree_map(input);
-
-    let len = tree_map.len();
-
-    // Iterate on all rows
-    (0..len)
-        // We map each row to the # of visible trees in the row
-        .map(|row_idx| {
-            // Iterate on column
-            (0..len)
-                // Filter on visible trees and count them
-                .filter(|col_idx| {
-                    let tree_height = tree_map[row_idx][*col_idx];
-
-                    // Visible from the left
-                    tree_map[row_idx][0..*col_idx]
-                        .iter()
-                        .all(|h| h < &tree_height) ||
-                    // Visible from the right
-                    tree_map[row_idx][col_idx + 1..len]
-                    .iter()
-                    .all(|h| h < &tree_height) ||
-                    // Visible from above
-                    tree_map[0..row_idx]
-                            .iter()
-                            .all(|h| h[*col_idx] < tree_height) ||
-                    // Visible from below
-                    tree_map[row_idx + 1..len]
-                            .iter()
-                            .all(|h| h[*col_idx] < tree_height)
-                })
-                .count()
-        })
-        // Sum each row
-        .sum()
+    visibility_map(&create_tree_map(input))
+        .iter()
+        .flatten()
+        .filter(|&&visible| visible)
+        .count()
 }
 
+/// Gets the best scenic score of the forest
+///
+/// # Examples
+///
+/// ```
+/// use advent_of_code_2022::day_8::get_max_scenic_score;
+///
+/// assert_eq!(get_max_scenic_score("30373\n25512\n65332\n33549\n35390"), 8);
+/// ```
 #[must_use]
 pub fn get_max_scenic_score(input: &str) -> usize {
-    let mut tree_map = create_tree_map(input);
+    best_scenic_spot(&create_tree_map(input)).map_or(0, |(_, _, score)| score)
+}
 
-    let len = tree_map.len();
+/// Returns, for each tree, whether it is visible from outside the forest
+///
+/// # Examples
+///
+/// ```
+/// use advent_of_code_2022::day_8::{create_tree_map, visibility_map};
+///
+/// let tree_map = create_tree_map("3337\n2151\n6533");
+///
+/// assert_eq!(visibility_map(&tree_map)[1], [true, false, true, true]);
+/// ```
+#[must_use]
+pub fn visibility_map(tree_map: &[Vec<u8>]) -> Vec<Vec<bool>> {
+    sweep_forest(tree_map, false, |visible, view| *visible |= view.visible)
+}
+
+/// Returns the scenic score of each tree
+///
+/// # Examples
+///
+/// ```
+/// use advent_of_code_2022::day_8::{create_tree_map, scenic_score_map};
+///
+/// let tree_map = create_tree_map("3337\n2151\n6533");
+///
+/// assert_eq!(scenic_score_map(&tree_map)[1], [0, 1, 2, 0]);
+/// ```
+#[must_use]
+pub fn scenic_score_map(tree_map: &[Vec<u8>]) -> Vec<Vec<usize>> {
+    sweep_forest(tree_map, 1, |score, view| *score *= view.distance)
+}
+
+/// Finds the tree with the best scenic score, as `(row, column, score)`
+///
+/// The first one in reading order wins ties, and an empty forest has no spot.
+///
+/// # Examples
+///
+/// ```
+/// use advent_of_code_2022::day_8::{best_scenic_spot, create_tree_map};
+///
+/// let tree_map = create_tree_map("30373\n25512\n65332\n33549\n35390");
+///
+/// assert_eq!(best_scenic_spot(&tree_map), Some((3, 2, 8)));
+/// ```
+#[must_use]
+pub fn best_scenic_spot(tree_map: &[Vec<u8>]) -> Option<(usize, usize, usize)> {
+    scenic_score_map(tree_map)
+        .into_iter()
+        .enumerate()
+        .flat_map(|(row_idx, row)| {
+            row.into_iter()
+                .enumerate()
+                .map(move |(col_idx, score)| (row_idx, col_idx, score))
+        })
+        // max_by_key keeps the last maximum, we want the first one
+        .rev()
+        .max_by_key(|&(_, _, score)| score)
+}
+
+// Sweeps the forest in the 4 directions and folds the view of each tree in every direction into a
+// map starting at `initial`, so callers only build the map they need.
+// Each sweep is linear, so the whole thing is O(trees count).
+fn sweep_forest<T: Clone>(
+    tree_map: &[Vec<u8>],
+    initial: T,
+    fold: impl Fn(&mut T, View),
+) -> Vec<Vec<T>> {
+    let height = tree_map.len();
+    let width = tree_map.first().map_or(0, Vec::len);
+
+    let mut map = vec![vec![initial; width]; height];
+
+    let mut apply = |row_idx: usize, col_idx: usize, view: View| {
+        fold(&mut map[row_idx][col_idx], view);
+    };
+
+    for (row_idx, row) in tree_map.iter().enumerate() {
+        // Looking left
+        for (col_idx, view) in sweep_line(row.iter().copied()).into_iter().enumerate() {
+            apply(row_idx, col_idx, view);
+        }
 
-    // We put all the borders at 10 to always count them as a limit
-    // A bit dirty but it makes it much simpler as there's no take_until function in std or itertools
-    for row in &mut tree_map {
-        row[0] = 10;
-        row[len - 1] = 10;
+        // Looking right
+        for (col_idx, view) in sweep_line(row.iter().rev().copied())
+            .into_iter()
+            .rev()
+            .enumerate()
+        {
+            apply(row_idx, col_idx, view);
+        }
     }
 
-    for col_idx in 0..len {
-        tree_map[0][col_idx] = 10;
-        tree_map[len - 1][col_idx] = 10;
+    for col_idx in 0..width {
+        let column = tree_map.iter().map(|row| row[col_idx]);
+
+        // Looking up
+        for (row_idx, view) in sweep_line(column.clone()).into_iter().enumerate() {
+            apply(row_idx, col_idx, view);
+        }
+
+        // Looking down
+        for (row_idx, view) in sweep_line(column.rev()).into_iter().rev().enumerate() {
+            apply(row_idx, col_idx, view);
+        }
     }
 
-    // We check everything except borders (always a score of 0 anyways)
-    (1..len - 1)
-        // We map each row to its maximum score
-        .map(|row_idx| {
-            (1..len - 1)
-                // We map each column to its score then use max() on it
-                .map(|col_idx| {
-                    let tree_height = &tree_map[row_idx][col_idx];
+    map
+}
 
-                    // This stops at most at the border, and we add 1 as we always see at least 1 tree
-                    let left_score = tree_map[row_idx][0..col_idx]
-                        .iter()
-                        .rev()
-                        .take_while(|h| h < &tree_height)
-                        .count()
-                        + 1;
+/// What a tree sees when looking towards the start of its line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct View {
+    /// All trees until the edge are shorter
+    visible: bool,
+    /// Number of trees seen, up to the first one at least as tall or the edge
+    distance: usize,
+}
 
-                    let right_score = tree_map[row_idx][col_idx + 1..len]
-                        .iter()
-                        .take_while(|h| h < &tree_height)
-                        .count()
-                        + 1;
+// Monotonic stack sweep: the stack holds the trees that can still block a view, from the tallest to
+// the shortest. A new tree pops all shorter ones, as they are hidden behind it from now on, and the
+// remaining top of the stack is the first tree at least as tall.
+fn sweep_line(heights: impl Iterator<Item = u8>) -> Vec<View> {
+    let mut stack: Vec<(usize, u8)> = vec![];
 
-                    let top_score = tree_map[0..row_idx]
-                        .iter()
-                        .rev()
-                        .take_while(|h| h[col_idx] < *tree_height)
-                        .count()
-                        + 1;
+    heights
+        .enumerate()
+        .map(|(idx, height)| {
+            while stack.last().is_some_and(|&(_, other)| other < height) {
+                stack.pop();
+            }
 
-                    let bottom_score = tree_map[row_idx + 1..len]
-                        .iter()
-                        .take_while(|h| h[col_idx] < *tree_height)
-                        .count()
-                        + 1;
+            let view = match stack.last() {
+                Some(&(blocker_idx, _)) => View {
+                    visible: false,
+                    distance: idx - blocker_idx,
+                },
+                None => View {
+                    visible: true,
+                    distance: idx,
+                },
+            };
 
-                    left_score * right_score * top_score * bottom_score
-                })
-                // Maxing on the column
-                .max()
-                .expect("No max found in column {col_idx}")
+            stack.push((idx, height));
+            view
         })
-        // Maxing on the row
-        .max()
-        .expect("No max found for input {tree_map}")
+        .collect()
 }
 
-fn create_tree_map(input: &str) -> Vec<Vec<u8>> {
-    let mut result = vec![];
+/// Parses the forest into rows of tree heights
+///
+/// # Panics
+/// Panics if a tree is not a digit, or if rows do not all have the same length
+#[must_use]
+pub fn create_tree_map(input: &str) -> Vec<Vec<u8>> {
+    let mut result: Vec<Vec<u8>> = vec![];
 
     for line in input.lines() {
         let mut row = vec![];
@@ -111,7 +193,19 @@ fn create_tree_map(input: &str) -> Vec<Vec<u8>> {
             row.push(
                 number
                     .to_digit(10)
-                    .expect("Cannot cast {number} as an integer") as u8,
+                    .unwrap_or_else(|| panic!("Cannot cast {number} as an integer"))
+                    as u8,
+            );
+        }
+
+        if let Some(first_row) = result.first() {
+            assert_eq!(
+                row.len(),
+                first_row.len(),
+                "Line {} has {} trees but the first one has {}",
+                result.len() + 1,
+                row.len(),
+                first_row.len()
             );
         }
 
@@ -121,9 +215,552 @@ fn create_tree_map(input: &str) -> Vec<Vec<u8>> {
     result
 }
 
+/// The 8 compass directions, for line-of-sight queries
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompassDirection {
+    North,
+    NorthEast,
+    East,
+    SouthEast,
+    South,
+    SouthWest,
+    West,
+    NorthWest,
+}
+
+impl CompassDirection {
+    pub const ALL: [Self; 8] = [
+        Self::North,
+        Self::NorthEast,
+        Self::East,
+        Self::SouthEast,
+        Self::South,
+        Self::SouthWest,
+        Self::West,
+        Self::NorthWest,
+    ];
+
+    /// Returns the step as `(rows, columns)`, north being the first row
+    #[must_use]
+    pub fn step(self) -> (isize, isize) {
+        match self {
+            Self::North => (-1, 0),
+            Self::NorthEast => (-1, 1),
+            Self::East => (0, 1),
+            Self::SouthEast => (1, 1),
+            Self::South => (1, 0),
+            Self::SouthWest => (1, -1),
+            Self::West => (0, -1),
+            Self::NorthWest => (-1, -1),
+        }
+    }
+}
+
+/// Returns the trees seen from a viewpoint along a rational slope, from the closest to the farthest.
+///
+/// The slope is given as a `(rows, columns)` step and only the trees exactly on the line are
+/// looked at, so `(2, 4)` is the same as `(1, 2)` and goes through every other column. Like for the
+/// scenic score, a tree at least as tall as the viewer is seen but blocks the rest of the line.
+///
+/// # Arguments
+/// * `tree_map` - The forest, see [`create_tree_map`]
+/// * `viewpoint` - `(row, column)` of the viewer, whose own tree is never part of the result
+/// * `height` - Height of the viewer's eyes, which can differ from the tree at the viewpoint
+/// * `slope` - `(rows, columns)` direction of the line
+///
+/// # Panics
+/// Panics if the viewpoint is outside the forest or if the slope is `(0, 0)`
+///
+/// # Examples
+///
+/// ```
+/// use advent_of_code_2022::day_8::{create_tree_map, line_of_sight, CompassDirection};
+///
+/// let tree_map = create_tree_map("30373\n25512\n65332\n33549\n35390");
+///
+/// // The tree of height 5 in the middle of the fourth row, looking up
+/// let seen = line_of_sight(&tree_map, (3, 2), 5, CompassDirection::North.step());
+/// assert_eq!(seen, [(2, 2), (1, 2)]);
+///
+/// // Up one row and right two columns, like a knight in chess
+/// assert_eq!(line_of_sight(&tree_map, (3, 0), 5, (-1, 2)), [(2, 2), (1, 4)]);
+/// ```
+#[must_use]
+pub fn line_of_sight(
+    tree_map: &[Vec<u8>],
+    viewpoint: (usize, usize),
+    height: u8,
+    slope: (isize, isize),
+) -> Vec<(usize, usize)> {
+    let (width, rows) = dimensions(tree_map);
+    assert!(
+        viewpoint.0 < rows && viewpoint.1 < width,
+        "Viewpoint {viewpoint:?} is outside the {rows}x{width} forest"
+    );
+    assert!(slope != (0, 0), "A line of sight needs a non-zero slope");
+
+    // We reduce the slope so we stop on every tree of the line
+    let divisor = gcd(slope.0.unsigned_abs(), slope.1.unsigned_abs()) as isize;
+    let step = (slope.0 / divisor, slope.1 / divisor);
+
+    let mut seen = vec![];
+    let mut position = viewpoint;
+
+    while let (Some(row), Some(col)) = (
+        position
+            .0
+            .checked_add_signed(step.0)
+            .filter(|&row| row < rows),
+        position
+            .1
+            .checked_add_signed(step.1)
+            .filter(|&col| col < width),
+    ) {
+        seen.push((row, col));
+
+        if tree_map[row][col] >= height {
+            break;
+        }
+
+        position = (row, col);
+    }
+
+    seen
+}
+
+/// Returns the trees seen from a viewpoint along the 8 compass directions, in the order of
+/// [`CompassDirection::ALL`]
+///
+/// # Panics
+/// Panics if the viewpoint is outside the forest
+#[must_use]
+pub fn visible_trees(
+    tree_map: &[Vec<u8>],
+    viewpoint: (usize, usize),
+    height: u8,
+) -> Vec<(usize, usize)> {
+    CompassDirection::ALL
+        .iter()
+        .flat_map(|direction| line_of_sight(tree_map, viewpoint, height, direction.step()))
+        .collect()
+}
+
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// Renders the tree heights as a binary PGM image, from black for 0 to white for 9
+///
+/// # Examples
+///
+/// ```
+/// use advent_of_code_2022::day_8::{create_tree_map, heightmap_pgm};
+///
+/// let image = heightmap_pgm(&create_tree_map("09\n90"));
+///
+/// assert_eq!(image, b"P5\n2 2\n255\n\x00\xff\xff\x00");
+/// ```
+#[must_use]
+pub fn heightmap_pgm(tree_map: &[Vec<u8>]) -> Vec<u8> {
+    let (width, height) = dimensions(tree_map);
+
+    let mut image = format!("P5\n{width} {height}\n255\n").into_bytes();
+    image.extend(tree_map.iter().flatten().map(|&tree| gray(tree)));
+    image
+}
+
+/// Renders the forest as a binary PPM image: the heightmap in gray, with visible trees in green
+#[must_use]
+pub fn visibility_ppm(tree_map: &[Vec<u8>]) -> Vec<u8> {
+    let visibility_map = visibility_map(tree_map);
+
+    let pixels = tree_map
+        .iter()
+        .flatten()
+        .zip(visibility_map.iter().flatten())
+        .map(|(&tree, &visible)| {
+            let gray = gray(tree);
+            if visible {
+                [gray / 2, 128 + gray / 2, gray / 2]
+            } else {
+                [gray; 3]
+            }
+        });
+
+    ppm(dimensions(tree_map), pixels)
+}
+
+/// Renders the scenic scores as a binary PPM heatmap, from black to red, yellow and white for the
+/// best score.
+///
+/// Scores span several orders of magnitude, so the scale is logarithmic.
+#[must_use]
+pub fn scenic_heatmap_ppm(tree_map: &[Vec<u8>]) -> Vec<u8> {
+    let scenic_score_map = scenic_score_map(tree_map);
+    let max_score = scenic_score_map
+        .iter()
+        .flatten()
+        .copied()
+        .max()
+        .unwrap_or(0);
+
+    let pixels = scenic_score_map.iter().flatten().map(|&score| {
+        let ratio = if max_score == 0 {
+            0.0
+        } else {
+            (score as f64).ln_1p() / (max_score as f64).ln_1p()
+        };
+
+        // Each channel lights up on its own third of the scale
+        [0.0, 1.0, 2.0].map(|offset| ((3.0 * ratio - offset).clamp(0.0, 1.0) * 255.0).round() as u8)
+    });
+
+    ppm(dimensions(tree_map), pixels)
+}
+
+// Returns (width, height)
+fn dimensions(tree_map: &[Vec<u8>]) -> (usize, usize) {
+    (tree_map.first().map_or(0, Vec::len), tree_map.len())
+}
+
+fn gray(tree: u8) -> u8 {
+    (u16::from(tree.min(9)) * 255 / 9) as u8
+}
+
+fn ppm((width, height): (usize, usize), pixels: impl Iterator<Item = [u8; 3]>) -> Vec<u8> {
+    let mut image = format!("P6\n{width} {height}\n255\n").into_bytes();
+    image.extend(pixels.flatten());
+    image
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_utils::Lcg;
+
+    // The first implementations, in O(n^3), kept as references
+    fn naive_visible_trees_count(input: &str) -> usize {
+        let tree_map = create_tree_map(input);
+
+        let height = tree_map.len();
+        let width = tree_map[0].len();
+
+        // Iterate on all rows
+        (0..height)
+            // We map each row to the # of visible trees in the row
+            .map(|row_idx| {
+                // Iterate on column
+                (0..width)
+                    // Filter on visible trees and count them
+                    .filter(|col_idx| {
+                        let tree_height = tree_map[row_idx][*col_idx];
+
+                        // Visible from the left
+                        tree_map[row_idx][0..*col_idx]
+                            .iter()
+                            .all(|h| h < &tree_height) ||
+                        // Visible from the right
+                        tree_map[row_idx][col_idx + 1..width]
+                        .iter()
+                        .all(|h| h < &tree_height) ||
+                        // Visible from above
+                        tree_map[0..row_idx]
+                                .iter()
+                                .all(|h| h[*col_idx] < tree_height) ||
+                        // Visible from below
+                        tree_map[row_idx + 1..height]
+                                .iter()
+                                .all(|h| h[*col_idx] < tree_height)
+                    })
+                    .count()
+            })
+            // Sum each row
+            .sum()
+    }
+
+    fn naive_max_scenic_score(input: &str) -> usize {
+        let mut tree_map = create_tree_map(input);
+
+        let height = tree_map.len();
+        let width = tree_map[0].len();
+
+        // We put all the borders at 10 to always count them as a limit
+        // A bit dirty but it makes it much simpler as there's no take_until function in std or itertools
+        for row in &mut tree_map {
+            row[0] = 10;
+            row[width - 1] = 10;
+        }
+
+        tree_map[0].fill(10);
+        tree_map[height - 1].fill(10);
+
+        // We check everything except borders (always a score of 0 anyways)
+        (1..height - 1)
+            // We map each row to its maximum score
+            .map(|row_idx| {
+                (1..width - 1)
+                    // We map each column to its score then use max() on it
+                    .map(|col_idx| {
+                        let tree_height = &tree_map[row_idx][col_idx];
+
+                        // This stops at most at the border, and we add 1 as we always see at least 1 tree
+                        let left_score = tree_map[row_idx][0..col_idx]
+                            .iter()
+                            .rev()
+                            .take_while(|h| h < &tree_height)
+                            .count()
+                            + 1;
+
+                        let right_score = tree_map[row_idx][col_idx + 1..width]
+                            .iter()
+                            .take_while(|h| h < &tree_height)
+                            .count()
+                            + 1;
+
+                        let top_score = tree_map[0..row_idx]
+                            .iter()
+                            .rev()
+                            .take_while(|h| h[col_idx] < *tree_height)
+                            .count()
+                            + 1;
+
+                        let bottom_score = tree_map[row_idx + 1..height]
+                            .iter()
+                            .take_while(|h| h[col_idx] < *tree_height)
+                            .count()
+                            + 1;
+
+                        left_score * right_score * top_score * bottom_score
+                    })
+                    // Maxing on the column
+                    .max()
+                    .expect("No max found in column {col_idx}")
+            })
+            // Maxing on the row
+            .max()
+            .expect("No max found for input {tree_map}")
+    }
+
+    // Pseudo-random forest
+    fn random_forest(height: usize, width: usize, seed: usize) -> String {
+        let mut lcg = Lcg::new(seed);
+
+        (0..height)
+            .map(|_| {
+                (0..width)
+                    // Few heights so there are many ties
+                    .map(|_| char::from(b'0' + lcg.below(4) as u8))
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn test_against_naive() {
+        for (height, width) in
+            (3..20).flat_map(|height| [(height, height), (height, 3), (3, height)])
+        {
+            for seed in 0..5 {
+                let forest = random_forest(height, width, seed);
+
+                assert_eq!(
+                    get_visible_trees_count(&forest),
+                    naive_visible_trees_count(&forest),
+                    "{forest}"
+                );
+                assert_eq!(
+                    get_max_scenic_score(&forest),
+                    naive_max_scenic_score(&forest),
+                    "{forest}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_sweep_line() {
+        let views: Vec<(bool, usize)> = sweep_line([3, 0, 3, 7, 3, 1, 3].into_iter())
+            .into_iter()
+            .map(|view| (view.visible, view.distance))
+            .collect();
+
+        assert_eq!(
+            views,
+            [
+                (true, 0),
+                (false, 1),
+                (false, 2),
+                (true, 3),
+                (false, 1),
+                (false, 1),
+                (false, 2)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rectangular() {
+        // The old code only looked at the first 3 columns
+        let forest = "3339\n3139\n3339";
+        let tree_map = create_tree_map(forest);
+
+        assert_eq!(
+            visibility_map(&tree_map),
+            [[true; 4], [true, false, false, true], [true; 4]]
+        );
+        assert_eq!(scenic_score_map(&tree_map), [[0; 4], [0, 1, 2, 0], [0; 4]]);
+        assert_eq!(best_scenic_spot(&tree_map), Some((1, 2, 2)));
+        assert_eq!(get_visible_trees_count(forest), 10);
+
+        let tall = create_tree_map("1\n5\n2\n5\n1");
+        assert_eq!(scenic_score_map(&tall), [[0], [0], [0], [0], [0]]);
+        assert_eq!(
+            visibility_map(&tall),
+            [[true], [true], [true], [true], [true]]
+        );
+    }
+
+    #[test]
+    fn test_degenerate_forests() {
+        assert_eq!(best_scenic_spot(&create_tree_map("")), None);
+        assert_eq!(get_max_scenic_score(""), 0);
+        assert_eq!(get_visible_trees_count(""), 0);
+        assert_eq!(get_visible_trees_count("7"), 1);
+        assert_eq!(get_max_scenic_score("7"), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Line 2 has 3 trees but the first one has 4")]
+    fn test_ragged_forest() {
+        let _ = create_tree_map("1234\n123");
+    }
+
+    #[test]
+    fn test_line_of_sight_matches_scenic_scores() {
+        let tree_map = create_tree_map(&random_forest(12, 17, 3));
+        let scenic_score_map = scenic_score_map(&tree_map);
+
+        for (row_idx, row) in tree_map.iter().enumerate() {
+            for (col_idx, &tree) in row.iter().enumerate() {
+                let score: usize = [
+                    CompassDirection::North,
+                    CompassDirection::East,
+                    CompassDirection::South,
+                    CompassDirection::West,
+                ]
+                .iter()
+                .map(|direction| {
+                    line_of_sight(&tree_map, (row_idx, col_idx), tree, direction.step()).len()
+                })
+                .product();
+
+                assert_eq!(score, scenic_score_map[row_idx][col_idx]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_line_of_sight() {
+        let tree_map = create_tree_map(DEMO_INPUT);
+
+        // Diagonals from the middle 3, blocked by taller or equal trees
+        assert_eq!(
+            line_of_sight(&tree_map, (2, 2), 3, CompassDirection::NorthWest.step()),
+            [(1, 1)]
+        );
+        assert_eq!(
+            line_of_sight(&tree_map, (2, 2), 3, CompassDirection::SouthWest.step()),
+            [(3, 1)]
+        );
+        assert_eq!(
+            line_of_sight(&tree_map, (2, 2), 4, CompassDirection::SouthWest.step()),
+            [(3, 1), (4, 0)]
+        );
+        // A higher viewpoint sees over the 5
+        assert_eq!(
+            line_of_sight(&tree_map, (2, 2), 6, CompassDirection::NorthWest.step()),
+            [(1, 1), (0, 0)]
+        );
+        // Slopes are reduced, and there is nothing past the edge
+        assert_eq!(
+            line_of_sight(&tree_map, (4, 0), 9, (-4, 2)),
+            line_of_sight(&tree_map, (4, 0), 9, (-2, 1))
+        );
+        assert_eq!(
+            line_of_sight(&tree_map, (4, 0), 9, (-2, 1)),
+            [(2, 1), (0, 2)]
+        );
+        assert_eq!(line_of_sight(&tree_map, (0, 0), 9, (-1, 3)), []);
+
+        // Edges see nothing towards the outside, and the middle sees its 8 neighbours at least
+        assert_eq!(visible_trees(&tree_map, (0, 0), 0).len(), 3);
+        assert_eq!(visible_trees(&tree_map, (2, 2), 0).len(), 8);
+        assert_eq!(visible_trees(&tree_map, (2, 2), 10).len(), 16);
+    }
+
+    #[test]
+    #[should_panic(expected = "Viewpoint (5, 0) is outside the 5x5 forest")]
+    fn test_line_of_sight_outside() {
+        let _ = visible_trees(&create_tree_map(DEMO_INPUT), (5, 0), 5);
+    }
+
+    #[test]
+    #[should_panic(expected = "non-zero slope")]
+    fn test_line_of_sight_zero_slope() {
+        let _ = line_of_sight(&create_tree_map(DEMO_INPUT), (1, 1), 5, (0, 0));
+    }
+
+    #[test]
+    fn test_heightmap_pgm() {
+        let image = heightmap_pgm(&create_tree_map(DEMO_INPUT));
+        let header = b"P5\n5 5\n255\n";
+
+        assert_eq!(&image[..header.len()], header);
+        assert_eq!(image.len(), header.len() + 25);
+        // 3, 0, 3, 7, 3
+        assert_eq!(&image[header.len()..header.len() + 5], [85, 0, 85, 198, 85]);
+    }
+
+    #[test]
+    fn test_visibility_ppm() {
+        let image = visibility_ppm(&create_tree_map(DEMO_INPUT));
+        let header = b"P6\n5 5\n255\n";
+        let pixel = |row: usize, col: usize| {
+            let start = header.len() + 3 * (row * 5 + col);
+            &image[start..start + 3]
+        };
+
+        assert_eq!(&image[..header.len()], header);
+        assert_eq!(image.len(), header.len() + 3 * 25);
+        // Visible 5 and hidden 1 in the second row
+        assert_eq!(pixel(1, 1), [70, 198, 70]);
+        assert_eq!(pixel(1, 3), [28, 28, 28]);
+    }
+
+    #[test]
+    fn test_scenic_heatmap_ppm() {
+        let image = scenic_heatmap_ppm(&create_tree_map(DEMO_INPUT));
+        let header = b"P6\n5 5\n255\n";
+        let pixel = |row: usize, col: usize| {
+            let start = header.len() + 3 * (row * 5 + col);
+            &image[start..start + 3]
+        };
+
+        assert_eq!(image.len(), header.len() + 3 * 25);
+        // Borders score 0, the best spot scores 8 and the 5 in the second row scores 1
+        assert_eq!(pixel(0, 0), [0, 0, 0]);
+        assert_eq!(pixel(3, 2), [255, 255, 255]);
+        assert_eq!(pixel(1, 1), [241, 0, 0]);
+
+        // A forest where all scores are 0 stays black
+        let image = scenic_heatmap_ppm(&create_tree_map("12\n34"));
+        assert!(image[b"P6\n2 2\n255\n".len()..].iter().all(|&c| c == 0));
+    }
 
     const DEMO_INPUT: &str = "30373
 25512
@@ -1,9 +1,29 @@
 use std::collections::HashSet;
 
-#[derive(Debug, PartialEq, Hash, Eq, Clone, Copy)]
-struct Position {
-    x: i32,
-    y: i32,
+#[derive(Debug, PartialEq, Hash, Eq, Clone, Copy, Default)]
+pub struct Position {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl Position {
+    /// Returns where a knot at this position goes when `leader` moved: one step towards it,
+    /// diagonally if needed, unless they still touch.
+    ///
+    /// Knots further apart need several steps to catch up, see [`Rope::step`].
+    #[must_use]
+    pub fn follow(self, leader: Self) -> Self {
+        let (dx, dy) = (leader.x - self.x, leader.y - self.y);
+
+        if dx.abs() <= 1 && dy.abs() <= 1 {
+            return self;
+        }
+
+        Self {
+            x: self.x + dx.signum(),
+            y: self.y + dy.signum(),
+        }
+    }
 }
 
 #[derive(Debug, PartialEq)]
@@ -13,174 +33,205 @@ struct Motion {
 }
 
 #[derive(Debug, PartialEq, Clone, Copy)]
-enum Direction {
+pub enum Direction {
     Up,
     Right,
     Left,
     Down,
+    UpRight,
+    UpLeft,
+    DownRight,
+    DownLeft,
+    /// A jump of any `(dx, dy)` vector
+    Vector(i32, i32),
 }
 
-pub fn get_visited_positions_short_rope(input: &str) -> usize {
-    let motions = get_motions(input);
-
-    let mut head_position = Position { x: 0, y: 0 };
-    let mut tail_position = Position { x: 0, y: 0 };
-    let mut visited_locations = HashSet::new();
-
-    visited_locations.insert(tail_position.clone());
-
-    for motion in motions {
-        for _ in 0..motion.distance {
-            (head_position, tail_position) =
-                move_once_short(head_position, tail_position, motion.direction);
-
-            // We clone here because the actual tail position will change
-            visited_locations.insert(tail_position.clone());
+impl Direction {
+    /// Returns the `(dx, dy)` move of the head, up being positive y
+    #[must_use]
+    pub fn step(self) -> (i32, i32) {
+        match self {
+            Self::Up => (0, 1),
+            Self::Right => (1, 0),
+            Self::Left => (-1, 0),
+            Self::Down => (0, -1),
+            Self::UpRight => (1, 1),
+            Self::UpLeft => (-1, 1),
+            Self::DownRight => (1, -1),
+            Self::DownLeft => (-1, -1),
+            Self::Vector(dx, dy) => (dx, dy),
         }
     }
+}
 
-    visited_locations.len()
+/// A rope of knots starting at (0, 0), where each knot follows the previous one and the first
+/// one is the head
+#[derive(Debug, Clone)]
+pub struct Rope {
+    knots: Vec<Position>,
+    // Positions visited by each knot
+    visited: Vec<HashSet<Position>>,
 }
 
-fn move_once_short(
-    head_position: Position,
-    tail_position: Position,
-    direction: Direction,
-) -> (Position, Position) {
-    let mut new_head = head_position;
-    let mut new_tail = tail_position;
-
-    // We first move the head
-    match direction {
-        Direction::Up => new_head.y += 1,
-        Direction::Right => new_head.x += 1,
-        Direction::Left => new_head.x -= 1,
-        Direction::Down => new_head.y -= 1,
-    }
+impl Rope {
+    /// # Panics
+    /// Panics if `knot_count` is 0, as a rope needs a head
+    #[must_use]
+    pub fn new(knot_count: usize) -> Self {
+        assert!(knot_count > 0, "A rope needs at least one knot");
 
-    // We move the tail if the distance to the head in any dimension is >= 2
-    if (new_head.x - new_tail.x).abs() >= 2 || (new_head.y - new_tail.y).abs() >= 2 {
-        // When a move happens, the non-advancing coordinate will always be the same as the head
-        match direction {
-            Direction::Up => {
-                new_tail.y += 1;
-                new_tail.x = new_head.x;
-            }
-            Direction::Right => {
-                new_tail.x += 1;
-                new_tail.y = new_head.y
-            }
-            Direction::Left => {
-                new_tail.x -= 1;
-                new_tail.y = new_head.y
-            }
-            Direction::Down => {
-                new_tail.y -= 1;
-                new_tail.x = new_head.x;
-            }
+        Self {
+            knots: vec![Position::default(); knot_count],
+            visited: vec![HashSet::from([Position::default()]); knot_count],
         }
     }
 
-    (new_head, new_tail)
-}
-
-pub fn get_visited_positions_long_rope(input: &str) -> usize {
-    let motions = get_motions(input);
-
-    // We make it an array because we know its lenght!
-    let mut rope = [Position { x: 0, y: 0 }; 10];
+    /// Returns the knots, from the head to the tail
+    #[must_use]
+    pub fn knots(&self) -> &[Position] {
+        &self.knots
+    }
 
-    let mut visited_locations = HashSet::new();
+    /// Returns the positions a knot visited, starting position included
+    ///
+    /// # Panics
+    /// Panics if the rope has no such knot
+    #[must_use]
+    pub fn visited(&self, knot: usize) -> &HashSet<Position> {
+        &self.visited[knot]
+    }
 
-    // We can access our rope by index since it's an array
-    visited_locations.insert(rope[9].clone());
+    /// Consumes the rope and returns the positions a knot visited, without copying them
+    ///
+    /// # Panics
+    /// Panics if the rope has no such knot
+    #[must_use]
+    pub fn into_visited(mut self, knot: usize) -> HashSet<Position> {
+        assert!(
+            knot < self.visited.len(),
+            "Knot {knot} is not in a rope of {} knots",
+            self.visited.len()
+        );
 
-    for motion in motions {
-        for _ in 0..motion.distance {
-            rope = move_once_long(rope, motion.direction);
+        self.visited.swap_remove(knot)
+    }
 
-            visited_locations.insert(rope[9].clone());
+    /// Moves the head by one step, and every other knot after it.
+    ///
+    /// When the head jumps far away, the next knot walks towards it one step at a time until they
+    /// touch, and the rest of the rope follows each of these steps.
+    pub fn step(&mut self, direction: Direction) {
+        let (dx, dy) = direction.step();
+        let head = &mut self.knots[0];
+
+        head.x += dx;
+        head.y += dy;
+        self.visited[0].insert(*head);
+
+        // We go down the rope while knots move, and back up to let the previous knot take its
+        // next step once the rest of the rope caught up
+        let mut i = 1;
+
+        while i > 0 {
+            let knot = self.knots.get(i).map(|knot| knot.follow(self.knots[i - 1]));
+
+            match knot {
+                Some(knot) if knot != self.knots[i] => {
+                    self.knots[i] = knot;
+                    self.visited[i].insert(knot);
+                    i += 1;
+                }
+                // The end of the rope, or a knot touching the previous one
+                _ => i -= 1,
+            }
         }
     }
-
-    visited_locations.len()
 }
 
-fn move_once_long(rope: [Position; 10], direction: Direction) -> [Position; 10] {
-    let mut rope = rope;
-
-    // We start by moving the head
-    match direction {
-        Direction::Up => rope[0].y += 1,
-        Direction::Right => rope[0].x += 1,
-        Direction::Left => rope[0].x -= 1,
-        Direction::Down => rope[0].y -= 1,
+/// Simulates a rope of `knot_count` knots and returns the positions visited by one of them
+///
+/// # Arguments
+/// * `input` - One motion per line, like `R 4` or `UL 2`, or a `dx,dy` jump like `3,-2`
+/// * `knot_count` - Number of knots of the rope, head included
+/// * `knot` - Index of the knot to follow, 0 being the head
+///
+/// # Panics
+/// Panics if `knot >= knot_count`, before reading any motion, or if a motion cannot be parsed
+///
+/// # Examples
+///
+/// ```
+/// use advent_of_code_2022::day_9::get_visited_positions;
+///
+/// // With 3 knots, the middle one is 1 step behind the head and the tail 2 steps behind
+/// assert_eq!(get_visited_positions("R 4", 3, 1).len(), 4);
+/// assert_eq!(get_visited_positions("R 4", 3, 2).len(), 3);
+/// ```
+#[must_use]
+pub fn get_visited_positions(input: &str, knot_count: usize, knot: usize) -> HashSet<Position> {
+    // Checked before simulating anything, rather than after the whole input
+    assert!(
+        knot < knot_count,
+        "Knot {knot} is not in a rope of {knot_count} knots"
+    );
+
+    let mut rope = Rope::new(knot_count);
+
+    for motion in get_motions(input) {
+        for _ in 0..motion.distance {
+            rope.step(motion.direction);
+        }
     }
 
-    for i in 0..9 {
-        // The head and tail are two consecutive nodes
-        let head = rope[i];
-        // We make that a mutable reference
-        let tail = &mut rope[i + 1];
-
-        // Going right
-        if head.x - tail.x == 2 {
-            if head.y > tail.y {
-                tail.y += 1;
-            } else if head.y < tail.y {
-                tail.y -= 1;
-            }
-
-            tail.x += 1;
-
-        // Going left
-        } else if head.x - tail.x == -2 {
-            if head.y > tail.y {
-                tail.y += 1;
-            } else if head.y < tail.y {
-                tail.y -= 1;
-            }
-
-            tail.x -= 1;
-
-        // Going up
-        } else if head.y - tail.y == 2 {
-            if head.x > tail.x {
-                tail.x += 1;
-            } else if head.x < tail.x {
-                tail.x -= 1;
-            }
-
-            tail.y += 1;
-
-        // Going down
-        } else if head.y - tail.y == -2 {
-            if head.x > tail.x {
-                tail.x += 1;
-            } else if head.x < tail.x {
-                tail.x -= 1;
-            }
+    rope.into_visited(knot)
+}
 
-            tail.y -= 1;
-        }
-    }
+#[must_use]
+pub fn get_visited_positions_short_rope(input: &str) -> usize {
+    get_visited_positions(input, 2, 1).len()
+}
 
-    rope
+#[must_use]
+pub fn get_visited_positions_long_rope(input: &str) -> usize {
+    get_visited_positions(input, 10, 9).len()
 }
 
 fn get_motions(input: &str) -> Vec<Motion> {
     input
         .lines()
         .map(|line| {
-            let (direction, distance) = line.split_once(' ').unwrap();
+            // Jumps happen once
+            if let Some((dx, dy)) = line.split_once(',') {
+                let coordinate = |value: &str| {
+                    value
+                        .trim()
+                        .parse::<i32>()
+                        .unwrap_or_else(|_| panic!("Cannot parse jump {line}"))
+                };
+
+                return Motion {
+                    direction: Direction::Vector(coordinate(dx), coordinate(dy)),
+                    distance: 1,
+                };
+            }
+
+            let (direction, distance) = line
+                .split_once(' ')
+                .unwrap_or_else(|| panic!("Cannot parse motion {line}"));
 
-            let distance = distance.parse::<i32>().unwrap();
+            let distance = distance
+                .parse::<i32>()
+                .unwrap_or_else(|_| panic!("Cannot parse distance {distance}"));
             let direction = match direction {
                 "U" => Direction::Up,
                 "R" => Direction::Right,
                 "L" => Direction::Left,
                 "D" => Direction::Down,
-                _ => panic!("Unknown direction"),
+                "UR" => Direction::UpRight,
+                "UL" => Direction::UpLeft,
+                "DR" => Direction::DownRight,
+                "DL" => Direction::DownLeft,
+                _ => panic!("Unknown direction {direction}"),
             };
 
             Motion {
@@ -228,61 +279,199 @@ R 2";
         );
     }
 
+    #[test]
+    fn test_get_diagonal_and_jump_motions() {
+        assert_eq!(
+            get_motions("UR 2\nDL 1\n3,-2\n-1, 0"),
+            vec![
+                Motion {
+                    direction: Direction::UpRight,
+                    distance: 2
+                },
+                Motion {
+                    direction: Direction::DownLeft,
+                    distance: 1
+                },
+                Motion {
+                    direction: Direction::Vector(3, -2),
+                    distance: 1
+                },
+                Motion {
+                    direction: Direction::Vector(-1, 0),
+                    distance: 1
+                }
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot parse jump 3,x")]
+    fn test_get_jump_panic() {
+        get_motions("3,x");
+    }
+
+    #[test]
+    fn test_diagonal_motions() {
+        let mut rope = Rope::new(3);
+
+        rope.step(Direction::UpRight);
+        rope.step(Direction::UpRight);
+
+        assert_eq!(
+            rope.knots(),
+            [
+                Position { x: 2, y: 2 },
+                Position { x: 1, y: 1 },
+                Position { x: 0, y: 0 }
+            ]
+        );
+
+        rope.step(Direction::DownRight);
+
+        assert_eq!(
+            rope.knots(),
+            [
+                Position { x: 3, y: 1 },
+                Position { x: 2, y: 1 },
+                Position { x: 1, y: 1 }
+            ]
+        );
+
+        // Diagonal moves don't change the single-step rules
+        assert_eq!(
+            get_visited_positions("UR 4\nDL 4", 2, 1),
+            get_visited_positions(
+                "U 1\nR 1\nU 1\nR 1\nU 1\nR 1\nU 1\nR 1\nD 1\nL 1\nD 1\nL 1\nD 1\nL 1\nD 1\nL 1",
+                2,
+                1
+            )
+        );
+    }
+
+    #[test]
+    fn test_jumps() {
+        let mut rope = Rope::new(3);
+
+        // Knots more than 2 apart catch up step by step
+        rope.step(Direction::Vector(5, 0));
+
+        assert_eq!(
+            rope.knots(),
+            [
+                Position { x: 5, y: 0 },
+                Position { x: 4, y: 0 },
+                Position { x: 3, y: 0 }
+            ]
+        );
+        assert_eq!(rope.visited(1).len(), 5);
+        assert_eq!(rope.visited(2).len(), 4);
+
+        // Diagonal steps first, then straight ones
+        rope.step(Direction::Vector(-2, 4));
+
+        assert_eq!(rope.knots()[0], Position { x: 3, y: 4 });
+        assert_eq!(rope.knots()[1], Position { x: 3, y: 3 });
+        assert_eq!(rope.knots()[2], Position { x: 3, y: 2 });
+        assert!(rope.visited(1).contains(&Position { x: 3, y: 1 }));
+        assert!(rope.visited(2).contains(&Position { x: 3, y: 1 }));
+
+        // A jump back to a touching position moves nothing else
+        rope.step(Direction::Vector(0, -1));
+        assert_eq!(rope.knots()[1], Position { x: 3, y: 3 });
+
+        assert_eq!(get_visited_positions("10,10", 10, 9).len(), 2);
+    }
+
     #[test]
     #[should_panic]
     fn test_get_motions_panic() {
         get_motions("W 4");
     }
 
+    const LARGER_INPUT: &str = "R 5
+U 8
+L 8
+D 3
+R 17
+D 10
+L 25
+U 20";
+
+    #[test]
+    fn test_second_part() {
+        assert_eq!(get_visited_positions_long_rope(INPUT), 1);
+        assert_eq!(get_visited_positions_long_rope(LARGER_INPUT), 36);
+    }
+
+    #[test]
+    fn test_any_knot() {
+        // The head visits every position on its path
+        assert_eq!(get_visited_positions(INPUT, 1, 0).len(), 21);
+        assert_eq!(
+            get_visited_positions(INPUT, 10, 1),
+            get_visited_positions(INPUT, 2, 1)
+        );
+
+        // Knots further down the rope visit fewer positions
+        let counts: Vec<usize> = (0..10)
+            .map(|knot| get_visited_positions(LARGER_INPUT, 10, knot).len())
+            .collect();
+        assert!(counts.windows(2).all(|pair| pair[0] >= pair[1]));
+        assert_eq!(counts[9], 36);
+    }
+
+    #[test]
+    #[should_panic(expected = "Knot 2 is not in a rope of 2 knots")]
+    fn test_missing_knot() {
+        let _ = get_visited_positions(INPUT, 2, 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Knot 3 is not in a rope of 3 knots")]
+    fn test_into_visited_missing_knot() {
+        let _ = Rope::new(3).into_visited(3);
+    }
+
     #[test]
     fn test_move_rope_short() {
-        let mut head_position = Position { x: 0, y: 0 };
-        let mut tail_position = Position { x: 0, y: 0 };
+        let mut rope = Rope::new(2);
 
-        (head_position, tail_position) =
-            move_once_short(head_position, tail_position, Direction::Right);
-        (head_position, tail_position) =
-            move_once_short(head_position, tail_position, Direction::Right);
-        (head_position, tail_position) =
-            move_once_short(head_position, tail_position, Direction::Right);
-        (head_position, tail_position) =
-            move_once_short(head_position, tail_position, Direction::Right);
+        for _ in 0..4 {
+            rope.step(Direction::Right);
+        }
 
-        assert_eq!(head_position, Position { x: 4, y: 0 });
-        assert_eq!(tail_position, Position { x: 3, y: 0 });
+        assert_eq!(rope.knots()[0], Position { x: 4, y: 0 });
+        assert_eq!(rope.knots()[1], Position { x: 3, y: 0 });
 
-        (head_position, tail_position) =
-            move_once_short(head_position, tail_position, Direction::Up);
+        rope.step(Direction::Up);
 
-        assert_eq!(head_position, Position { x: 4, y: 1 });
-        assert_eq!(tail_position, Position { x: 3, y: 0 });
+        assert_eq!(rope.knots()[0], Position { x: 4, y: 1 });
+        assert_eq!(rope.knots()[1], Position { x: 3, y: 0 });
 
-        (head_position, tail_position) =
-            move_once_short(head_position, tail_position, Direction::Up);
+        rope.step(Direction::Up);
 
-        assert_eq!(head_position, Position { x: 4, y: 2 });
-        assert_eq!(tail_position, Position { x: 4, y: 1 });
+        assert_eq!(rope.knots()[0], Position { x: 4, y: 2 });
+        assert_eq!(rope.knots()[1], Position { x: 4, y: 1 });
     }
 
     #[test]
     fn test_move_rope_long() {
-        let mut rope = [Position { x: 0, y: 0 }; 10];
+        let mut rope = Rope::new(10);
 
-        rope = move_once_long(rope, Direction::Right);
-        rope = move_once_long(rope, Direction::Right);
-        rope = move_once_long(rope, Direction::Right);
-        rope = move_once_long(rope, Direction::Right);
+        for _ in 0..4 {
+            rope.step(Direction::Right);
+        }
 
-        assert_eq!(rope[0], Position { x: 4, y: 0 });
-        assert_eq!(rope[1], Position { x: 3, y: 0 });
-        assert_eq!(rope[9], Position { x: 0, y: 0 });
+        assert_eq!(rope.knots()[0], Position { x: 4, y: 0 });
+        assert_eq!(rope.knots()[1], Position { x: 3, y: 0 });
+        assert_eq!(rope.knots()[9], Position { x: 0, y: 0 });
 
-        rope = move_once_long(rope, Direction::Up);
+        rope.step(Direction::Up);
 
-        assert_eq!(rope[0], Position { x: 4, y: 1 });
-        assert_eq!(rope[1], Position { x: 3, y: 0 });
+        assert_eq!(rope.knots()[0], Position { x: 4, y: 1 });
+        assert_eq!(rope.knots()[1], Position { x: 3, y: 0 });
 
-        rope = move_once_long(rope, Direction::Up);
+        rope.step(Direction::Up);
 
         // Cf test example:
         // ......
@@ -290,11 +479,11 @@ R 2";
         // ....H.
         // .4321.
         // 5.....  (5 covers 6, 7, 8, 9, s)
-        assert_eq!(rope[0], Position { x: 4, y: 2 });
-        assert_eq!(rope[1], Position { x: 4, y: 1 });
-        assert_eq!(rope[2], Position { x: 3, y: 1 });
-        assert_eq!(rope[3], Position { x: 2, y: 1 });
-        assert_eq!(rope[4], Position { x: 1, y: 1 });
-        assert_eq!(rope[5], Position { x: 0, y: 0 });
+        assert_eq!(rope.knots()[0], Position { x: 4, y: 2 });
+        assert_eq!(rope.knots()[1], Position { x: 4, y: 1 });
+        assert_eq!(rope.knots()[2], Position { x: 3, y: 1 });
+        assert_eq!(rope.knots()[3], Position { x: 2, y: 1 });
+        assert_eq!(rope.knots()[4], Position { x: 1, y: 1 });
+        assert_eq!(rope.knots()[5], Position { x: 0, y: 0 });
     }
 }
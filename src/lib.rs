@@ -1,5 +1,3 @@
-#![feature(iter_array_chunks)]
-
 pub mod day_1;
 pub mod day_10;
 pub mod day_11;
@@ -15,3 +13,6 @@ pub mod day_6;
 pub mod day_7;
 pub mod day_8;
 pub mod day_9;
+
+#[cfg(test)]
+mod test_utils;
@@ -2,7 +2,7 @@ use advent_of_code_2022::{
     day_1, day_10, day_11, day_12, day_13, day_14, day_15, day_2, day_3, day_4, day_5, day_6,
     day_7, day_8, day_9,
 };
-use std::{fmt::Display, fs};
+use std::{env, fmt::Display, fs, path::Path, process};
 
 fn get_day_input(day: u8) -> String {
     let file_name = format!("data/day_{day}.txt");
@@ -12,6 +12,155 @@ fn get_day_input(day: u8) -> String {
 }
 
 fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    match args.first().map(String::as_str) {
+        None => run_all_days(),
+        Some("crane") => run_crane(&args[1..]),
+        Some("forest") => run_forest(&args[1..]),
+        Some(command @ ("tree" | "du" | "find" | "top" | "plan" | "json" | "materialise")) => {
+            run_filesystem(command, &args[1..])
+        }
+        Some(command) => {
+            eprintln!(
+                "Unknown command: {command}\n\
+                 Usage: advent_of_code_2022 [crane <name> | forest <dir> | tree | du | find | top | plan | json | materialise]"
+            );
+            process::exit(1);
+        }
+    }
+}
+
+/// Runs day 5 with the crane given by name
+fn run_crane(args: &[String]) {
+    let Some(crane) = args.first().and_then(|name| day_5::crane_from_name(name)) else {
+        eprintln!(
+            "Usage: advent_of_code_2022 crane <name>\n\
+             Known cranes: v1, v2, max-lift-N, rotating-N, metered-N-<crane>"
+        );
+        process::exit(1);
+    };
+
+    let (top_crates, cost) = day_5::find_top_crates_and_cost(&get_day_input(5), &crane);
+
+    println!("Day 5 result with crane {}:\n{top_crates}", crane.name());
+    println!("Total cost:\n{cost}");
+}
+
+/// Writes the day 8 images in a directory
+fn run_forest(args: &[String]) {
+    let [dir] = args else {
+        usage_error("Usage: advent_of_code_2022 forest <output directory>");
+    };
+
+    let tree_map = day_8::create_tree_map(&get_day_input(8));
+    let images = [
+        ("heights.pgm", day_8::heightmap_pgm(&tree_map)),
+        ("visible.ppm", day_8::visibility_ppm(&tree_map)),
+        ("scenic.ppm", day_8::scenic_heatmap_ppm(&tree_map)),
+    ];
+
+    for (name, image) in images {
+        let path = Path::new(dir).join(name);
+
+        if let Err(err) = fs::create_dir_all(dir).and_then(|()| fs::write(&path, image)) {
+            eprintln!("Cannot write {}: {err}", path.display());
+            process::exit(1);
+        }
+
+        println!("{}", path.display());
+    }
+}
+
+const FILESYSTEM_USAGE: &str = "Usage: advent_of_code_2022 <command> on the day 7 filesystem
+  tree
+  du [-d <depth>]
+  find [-min <size>] [-max <size>] [-name <glob>] [-type d|f]
+  top [<count>]
+  plan [<max deletions>]
+  json
+  materialise";
+
+/// Runs a report on the day 7 filesystem
+fn run_filesystem(command: &str, args: &[String]) {
+    let fs = day_7::FileSystem::from_transcript(&get_day_input(7));
+    let print_entries = |entries: Vec<day_7::EntryId>| {
+        for id in entries {
+            println!("{}\t{}", day_7::human_size(fs.size(id)), fs.path(id));
+        }
+    };
+
+    match (command, args) {
+        ("tree", []) => print!("{}", fs.tree()),
+        ("du", []) => print_entries(fs.du(usize::MAX)),
+        ("du", [flag, depth]) if flag == "-d" => print_entries(fs.du(parse_number(depth))),
+        ("find", _) => print_entries(fs.find(&parse_predicates(args))),
+        ("top", []) => run_top(&fs, 10),
+        ("top", [count]) => run_top(&fs, parse_number(count)),
+        ("plan", []) => run_plan(&fs, 3),
+        ("plan", [count]) => run_plan(&fs, parse_number(count)),
+        ("json", []) => println!("{}", fs.to_json()),
+        ("materialise", []) => match fs.materialise_in_temp_dir() {
+            Ok(dir) => println!("{}", dir.display()),
+            Err(err) => {
+                eprintln!("Cannot materialise the filesystem: {err}");
+                process::exit(1);
+            }
+        },
+        _ => usage_error(FILESYSTEM_USAGE),
+    }
+}
+
+fn run_top(fs: &day_7::FileSystem, count: usize) {
+    println!("Largest directories:");
+    for id in fs.largest_directories(count) {
+        println!("{}\t{}", day_7::human_size(fs.size(id)), fs.path(id));
+    }
+
+    println!("Largest files:");
+    for id in fs.largest_files(count) {
+        println!("{}\t{}", day_7::human_size(fs.size(id)), fs.path(id));
+    }
+}
+
+/// Plans the deletions for the day 7 update
+fn run_plan(fs: &day_7::FileSystem, max_items: usize) {
+    let Some(plan) = fs.plan_deletion(70_000_000, 30_000_000, max_items) else {
+        println!("Cannot free enough space with {max_items} deletions");
+        return;
+    };
+
+    for path in &plan.paths {
+        println!("{path}");
+    }
+    println!("Deleted size:\n{}", plan.deleted_size);
+    println!("Free space:\n{}", plan.free_space);
+}
+
+fn parse_predicates(args: &[String]) -> Vec<day_7::Predicate> {
+    args.chunks(2)
+        .map(|pair| match pair {
+            [flag, size] if flag == "-min" => day_7::Predicate::SizeAtLeast(parse_number(size)),
+            [flag, size] if flag == "-max" => day_7::Predicate::SizeAtMost(parse_number(size)),
+            [flag, glob] if flag == "-name" => day_7::Predicate::Name(glob.clone()),
+            [flag, kind] if flag == "-type" && kind == "d" => day_7::Predicate::Directory,
+            [flag, kind] if flag == "-type" && kind == "f" => day_7::Predicate::File,
+            _ => usage_error(FILESYSTEM_USAGE),
+        })
+        .collect()
+}
+
+fn parse_number(arg: &str) -> usize {
+    arg.parse()
+        .unwrap_or_else(|_| usage_error(&format!("Not a number: {arg}\n{FILESYSTEM_USAGE}")))
+}
+
+fn usage_error(usage: &str) -> ! {
+    eprintln!("{usage}");
+    process::exit(1);
+}
+
+fn run_all_days() {
     for i in 1..=17 {
         // We read the input from the file
         let input = get_day_input(i);
@@ -0,0 +1,17 @@
+//! Helpers shared by the tests of several days
+
+/// Linear congruential generator, so tests get the same pseudo-random fixtures on every run
+pub struct Lcg(usize);
+
+impl Lcg {
+    pub const fn new(seed: usize) -> Self {
+        Self(seed)
+    }
+
+    /// Returns a pseudo-random number in `0..modulo`
+    pub fn below(&mut self, modulo: usize) -> usize {
+        self.0 = (self.0 * 1_103_515_245 + 12_345) % (1 << 31);
+        // The low bits cycle quickly, the high ones are more random
+        (self.0 >> 16) % modulo
+    }
+}